@@ -2,9 +2,10 @@ use std::collections::HashMap;
 
 use byteorder::ByteOrder;
 
+use crate::compression::Compression;
 use crate::message::Message;
-use crate::types::PublishedMessage;
 use crate::types::PublishingError;
+use crate::types::{PublishedEntry, PublishedMessage, PublishedSubEntry};
 use crate::ResponseCode;
 use crate::{error::DecodeError, types::Header};
 
@@ -96,7 +97,7 @@ impl Decoder for Header {
         let (input, key) = read_u16(input)?;
         let (input, version) = read_u16(input)?;
 
-        Ok((input, Header::new(extract_response_code(key), version)))
+        Ok((input, Header::new(key, version)))
     }
 }
 
@@ -109,6 +110,46 @@ impl Decoder for PublishedMessage {
     }
 }
 
+impl Decoder for PublishedEntry {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
+        let (rest, publishing_id) = u64::decode(input)?;
+        let (rest, entry_type) = u8::decode(rest)?;
+
+        if entry_type & 0x80 == 0 {
+            let (input, message) = PublishedMessage::decode(input)?;
+            return Ok((input, PublishedEntry::Simple(message)));
+        }
+
+        let compression = Compression::from_code((entry_type & 0x70) >> 4)?;
+        let (input, records) = u16::decode(rest)?;
+        let (input, uncompressed_size) = u32::decode(input)?;
+        let (input, data) = read_vec::<u8>(input)?;
+        Ok((
+            input,
+            PublishedEntry::SubEntry(PublishedSubEntry {
+                publishing_id,
+                compression,
+                records,
+                uncompressed_size,
+                data,
+            }),
+        ))
+    }
+}
+
+impl Decoder for Vec<PublishedEntry> {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
+        let (mut input, len) = u32::decode(input)?;
+        let mut result = Vec::new();
+        for _ in 0..len {
+            let (input1, entry) = PublishedEntry::decode(input)?;
+            result.push(entry);
+            input = input1
+        }
+        Ok((input, result))
+    }
+}
+
 impl Decoder for Vec<PublishedMessage> {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
         let (mut input, len) = u32::decode(input)?;
@@ -122,7 +163,7 @@ impl Decoder for Vec<PublishedMessage> {
     }
 }
 
-fn extract_response_code(code: u16) -> u16 {
+pub fn extract_response_code(code: u16) -> u16 {
     code & 0b0111_1111_1111_1111
 }
 
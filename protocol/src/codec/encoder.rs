@@ -2,7 +2,12 @@ use std::{collections::HashMap, io::Write};
 
 use byteorder::{BigEndian, WriteBytesExt};
 
-use crate::{error::EncodeError, types::Header, types::PublishedMessage, ResponseCode};
+use crate::{
+    error::EncodeError,
+    types::Header,
+    types::{PublishedEntry, PublishedMessage, PublishedSubEntry},
+    ResponseCode,
+};
 
 use crate::types::PublishingError;
 
@@ -111,6 +116,51 @@ impl Encoder for PublishedMessage {
     }
 }
 
+impl Encoder for PublishedSubEntry {
+    fn encoded_size(&self) -> u32 {
+        self.publishing_id.encoded_size() + 1 + 2 + 4 + 4 + self.data.len() as u32
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.publishing_id.encode(writer)?;
+        writer.write_u8(0x80 | self.compression.code() << 4)?;
+        self.records.encode(writer)?;
+        self.uncompressed_size.encode(writer)?;
+        self.data.encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Encoder for PublishedEntry {
+    fn encoded_size(&self) -> u32 {
+        match self {
+            PublishedEntry::Simple(message) => message.encoded_size(),
+            PublishedEntry::SubEntry(entry) => entry.encoded_size(),
+        }
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        match self {
+            PublishedEntry::Simple(message) => message.encode(writer),
+            PublishedEntry::SubEntry(entry) => entry.encode(writer),
+        }
+    }
+}
+
+impl Encoder for Vec<PublishedEntry> {
+    fn encoded_size(&self) -> u32 {
+        4 + self.iter().fold(0, |acc, v| acc + v.encoded_size())
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        writer.write_u32::<BigEndian>(self.len() as u32)?;
+        for x in self {
+            x.encode(writer)?;
+        }
+        Ok(())
+    }
+}
+
 impl Encoder for Vec<PublishedMessage> {
     fn encoded_size(&self) -> u32 {
         4 + self.iter().fold(0, |acc, v| acc + v.encoded_size())
@@ -9,7 +9,7 @@ ResponseCode => uint16
 use std::io::Write;
 
 use crate::{
-    codec::{Decoder, Encoder},
+    codec::{encoder::encode_response_code, Decoder, Encoder},
     error::{DecodeError, EncodeError},
     protocol::commands::COMMAND_CLOSE,
     FromResponse, ResponseCode,
@@ -17,9 +17,6 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct CloseRequest {
@@ -36,6 +33,18 @@ impl CloseRequest {
             closing_reason,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn closing_code(&self) -> &ResponseCode {
+        &self.closing_code
+    }
+
+    pub fn closing_reason(&self) -> &str {
+        &self.closing_reason
+    }
 }
 
 impl Encoder for CloseRequest {
@@ -121,6 +130,12 @@ impl Decoder for CloseResponse {
     }
 }
 
+impl Command for CloseResponse {
+    fn key(&self) -> u16 {
+        encode_response_code(COMMAND_CLOSE)
+    }
+}
+
 impl FromResponse for CloseResponse {
     fn from_response(response: crate::Response) -> Option<Self> {
         match response.kind {
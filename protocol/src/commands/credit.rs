@@ -1,8 +1,5 @@
 use std::io::Write;
 
-#[cfg(test)]
-use fake::Fake;
-
 use crate::{
     codec::{Decoder, Encoder},
     error::{DecodeError, EncodeError},
@@ -8,9 +8,6 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct DeclarePublisherCommand {
@@ -2,6 +2,7 @@ use std::io::Write;
 
 use super::Command;
 use crate::codec::decoder::read_vec;
+use crate::compression::Compression;
 use crate::message::Message;
 use crate::{
     codec::{Decoder, Encoder},
@@ -9,11 +10,8 @@ use crate::{
     protocol::commands::COMMAND_DELIVER,
 };
 use byteorder::{BigEndian, WriteBytesExt};
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct DeliverCommand {
     pub subscription_id: u8,
     magic_version: i8,
@@ -26,6 +24,12 @@ pub struct DeliverCommand {
     trailer_length: u32,
     reserved: u32,
     pub messages: Vec<Message>,
+    /// Data of the chunk, kept to check its CRC on demand
+    #[cfg_attr(test, dummy(default))]
+    data: Vec<u8>,
+    /// Set when the entries of the chunk could not be read and its CRC does not match
+    #[cfg_attr(test, dummy(default))]
+    corrupted: bool,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -55,8 +59,49 @@ impl DeliverCommand {
             trailer_length,
             reserved,
             messages,
+            data: vec![],
+            corrupted: false,
         }
     }
+
+    /// Creation time of the chunk, in milliseconds since the epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Epoch of the stream leader that wrote the chunk
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Check that the CRC32 of the chunk data matches the one sent by the server
+    pub fn is_crc_valid(&self) -> bool {
+        !self.corrupted && crc32fast::hash(&self.data) as i32 == self.chunk_crc
+    }
+
+    /// Check if the entries of the chunk could not be read because its data is corrupted,
+    /// the chunk then has no messages
+    pub fn is_corrupted(&self) -> bool {
+        self.corrupted
+    }
+}
+
+impl PartialEq for DeliverCommand {
+    fn eq(&self, other: &Self) -> bool {
+        // the data is the encoding of the messages
+        self.subscription_id == other.subscription_id
+            && self.magic_version == other.magic_version
+            && self.chunk_type == other.chunk_type
+            && self.num_entries == other.num_entries
+            && self.timestamp == other.timestamp
+            && self.epoch == other.epoch
+            && self.chunk_first_offset == other.chunk_first_offset
+            && self.chunk_crc == other.chunk_crc
+            && self.trailer_length == other.trailer_length
+            && self.reserved == other.reserved
+            && self.messages == other.messages
+            && self.corrupted == other.corrupted
+    }
 }
 
 impl Encoder for DeliverCommand {
@@ -74,8 +119,8 @@ impl Encoder for DeliverCommand {
             + self.reserved.encoded_size()
             + 4 // vec of messages
             + self.messages.iter().fold(0, |acc, message| {
-                acc + 1 +  message.encoded_size()
-            }) as u32
+                acc + 4 + message.encoded_size()
+            })
     }
 
     fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
@@ -92,9 +137,9 @@ impl Encoder for DeliverCommand {
         let size = self
             .messages
             .iter()
-            .fold(0, |acc, message| acc + 1 + message.encoded_size());
+            .fold(0, |acc, message| acc + 4 + message.encoded_size());
 
-        writer.write_u32::<BigEndian>(size as u32)?;
+        writer.write_u32::<BigEndian>(size)?;
         self.trailer_length.encode(writer)?;
         self.reserved.encode(writer)?;
 
@@ -117,16 +162,29 @@ impl Decoder for DeliverCommand {
         let (input, epoch) = u64::decode(input)?;
         let (input, chunk_first_offset) = u64::decode(input)?;
         let (input, chunk_crc) = i32::decode(input)?;
-        let (input, _data_length) = u32::decode(input)?;
+        let (input, data_length) = u32::decode(input)?;
         let (input, trailer_length) = u32::decode(input)?;
-        let (mut input, reserved) = u32::decode(input)?;
+        let (input, reserved) = u32::decode(input)?;
+
+        let data = input
+            .get(..data_length as usize)
+            .ok_or(DecodeError::Incomplete(data_length as usize))?;
+        let input = &input[data.len()..];
 
         let mut messages = Vec::with_capacity(num_records as usize);
-        for _ in 0..num_records {
-            let (input1, result) = read_vec(input)?;
-            let (_, message) = Message::decode(&result)?;
-            messages.push(message);
-            input = input1;
+        let mut entries = data;
+        let mut corrupted = false;
+        while messages.len() < num_records as usize {
+            match read_entry(entries, &mut messages) {
+                Ok(rest) => entries = rest,
+                // reported as a CRC mismatch rather than failing the connection
+                Err(_) if crc32fast::hash(data) as i32 != chunk_crc => {
+                    messages.clear();
+                    corrupted = true;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
         }
 
         Ok((
@@ -143,11 +201,40 @@ impl Decoder for DeliverCommand {
                 trailer_length,
                 reserved,
                 messages,
+                data: data.to_vec(),
+                corrupted,
             },
         ))
     }
 }
 
+/// Read a simple entry or all the records packed in a sub-entry
+fn read_entry<'a>(input: &'a [u8], messages: &mut Vec<Message>) -> Result<&'a [u8], DecodeError> {
+    let (rest, entry_type) = u8::decode(input)?;
+
+    if entry_type & 0x80 == 0 {
+        let (input, result) = read_vec(input)?;
+        let (_, message) = Message::decode(&result)?;
+        messages.push(message);
+        return Ok(input);
+    }
+
+    let compression = Compression::from_code((entry_type & 0x70) >> 4)?;
+    let (input, records) = u16::decode(rest)?;
+    let (input, uncompressed_size) = u32::decode(input)?;
+    let (input, data) = read_vec::<u8>(input)?;
+    let data = compression.decompress(&data, uncompressed_size as usize)?;
+
+    let mut records_input = data.as_slice();
+    for _ in 0..records {
+        let (rest, result) = read_vec(records_input)?;
+        let (_, message) = Message::decode(&result)?;
+        messages.push(message);
+        records_input = rest;
+    }
+    Ok(input)
+}
+
 impl Command for DeliverCommand {
     fn key(&self) -> u16 {
         COMMAND_DELIVER
@@ -158,7 +245,10 @@ impl Command for DeliverCommand {
 mod tests {
     use fake::{Dummy, Faker};
 
+    use crate::codec::{Decoder, Encoder};
     use crate::commands::tests::command_encode_decode_test;
+    use crate::compression::Compression;
+    use crate::types::PublishedSubEntry;
     use ntex_amqp_codec::Message as AmpqMessage;
 
     use super::{DeliverCommand, Message};
@@ -175,4 +265,89 @@ mod tests {
     fn deliver_request_test() {
         command_encode_decode_test::<DeliverCommand>();
     }
+
+    #[test]
+    fn deliver_sub_entry_test() {
+        let simple = Message::builder().body("simple").build();
+        let packed = vec![
+            Message::builder().body("first").build(),
+            Message::builder().body("second").build(),
+        ];
+        let sub_entry = PublishedSubEntry::new(1, Compression::Gzip, &packed).unwrap();
+
+        let mut entries = vec![];
+        simple.encoded_size().encode(&mut entries).unwrap();
+        simple.encode(&mut entries).unwrap();
+        (0x80 | Compression::Gzip.code() << 4)
+            .encode(&mut entries)
+            .unwrap();
+        sub_entry.records.encode(&mut entries).unwrap();
+        sub_entry.uncompressed_size.encode(&mut entries).unwrap();
+        sub_entry.data.encode(&mut entries).unwrap();
+
+        let buffer = chunk(3, crc32fast::hash(&entries) as i32, &entries);
+
+        let (remaining, deliver) = DeliverCommand::decode(&buffer).unwrap();
+
+        assert!(remaining.is_empty());
+        assert!(deliver.is_crc_valid());
+        assert_eq!(
+            vec![
+                Some(&b"simple"[..]),
+                Some(&b"first"[..]),
+                Some(&b"second"[..])
+            ],
+            deliver
+                .messages
+                .iter()
+                .map(|message| message.data())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    fn chunk(num_records: u32, crc: i32, entries: &[u8]) -> Vec<u8> {
+        let mut buffer = vec![];
+        1u8.encode(&mut buffer).unwrap(); // subscription id
+        0i8.encode(&mut buffer).unwrap(); // magic version
+        0u8.encode(&mut buffer).unwrap(); // chunk type
+        (num_records as u16).encode(&mut buffer).unwrap(); // num entries
+        num_records.encode(&mut buffer).unwrap(); // num records
+        0u64.encode(&mut buffer).unwrap(); // timestamp
+        0u64.encode(&mut buffer).unwrap(); // epoch
+        0u64.encode(&mut buffer).unwrap(); // chunk first offset
+        crc.encode(&mut buffer).unwrap(); // chunk crc
+        (entries.len() as u32).encode(&mut buffer).unwrap(); // data length
+        0u32.encode(&mut buffer).unwrap(); // trailer length
+        0u32.encode(&mut buffer).unwrap(); // reserved
+        buffer.extend_from_slice(entries);
+        buffer
+    }
+
+    #[test]
+    fn deliver_crc_mismatch_test() {
+        let buffer = chunk(0, crc32fast::hash(b"original") as i32, b"corrupted");
+
+        let (remaining, deliver) = DeliverCommand::decode(&buffer).unwrap();
+
+        assert!(remaining.is_empty());
+        assert!(!deliver.is_crc_valid());
+        assert!(!deliver.is_corrupted());
+    }
+
+    #[test]
+    fn deliver_corrupted_entries_test() {
+        // an entry announcing more bytes than the chunk holds
+        let entries = [0u8, 0, 0, 100, 1];
+
+        let buffer = chunk(1, crc32fast::hash(b"original") as i32, &entries);
+        let (remaining, deliver) = DeliverCommand::decode(&buffer).unwrap();
+
+        assert!(remaining.is_empty());
+        assert!(deliver.is_corrupted());
+        assert!(!deliver.is_crc_valid());
+        assert!(deliver.messages.is_empty());
+
+        let buffer = chunk(1, crc32fast::hash(&entries) as i32, &entries);
+        assert!(DeliverCommand::decode(&buffer).is_err());
+    }
 }
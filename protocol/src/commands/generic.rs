@@ -6,9 +6,6 @@ use crate::{
     FromResponse, ResponseCode, ResponseKind,
 };
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct GenericResponse {
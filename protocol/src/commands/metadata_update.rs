@@ -5,16 +5,25 @@ use crate::{
 use super::Command;
 
 use crate::codec::Encoder;
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct MetadataUpdateCommand {
     code: ResponseCode,
     stream: String,
 }
 
+impl MetadataUpdateCommand {
+    /// Reason of the update, the stream moved or was deleted
+    pub fn code(&self) -> &ResponseCode {
+        &self.code
+    }
+
+    /// Stream whose publishers and subscriptions were removed by the server
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+}
+
 impl Decoder for MetadataUpdateCommand {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
         let (input, code) = ResponseCode::decode(input)?;
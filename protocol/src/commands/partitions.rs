@@ -0,0 +1,145 @@
+use std::io::Write;
+
+use crate::{
+    codec::{Decoder, Encoder},
+    error::{DecodeError, EncodeError},
+    protocol::commands::COMMAND_PARTITIONS,
+    FromResponse, ResponseCode,
+};
+
+use super::Command;
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct PartitionsRequest {
+    correlation_id: u32,
+    super_stream: String,
+}
+
+impl PartitionsRequest {
+    pub fn new(correlation_id: u32, super_stream: String) -> Self {
+        Self {
+            correlation_id,
+            super_stream,
+        }
+    }
+}
+
+impl Encoder for PartitionsRequest {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size() + self.super_stream.as_str().encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.super_stream.as_str().encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Command for PartitionsRequest {
+    fn key(&self) -> u16 {
+        COMMAND_PARTITIONS
+    }
+}
+
+impl Decoder for PartitionsRequest {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, super_stream) = Option::decode(input)?;
+
+        Ok((
+            input,
+            PartitionsRequest {
+                correlation_id,
+                super_stream: super_stream.unwrap(),
+            },
+        ))
+    }
+}
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct PartitionsResponse {
+    pub correlation_id: u32,
+    response_code: ResponseCode,
+    streams: Vec<String>,
+}
+
+impl PartitionsResponse {
+    pub fn new(correlation_id: u32, response_code: ResponseCode, streams: Vec<String>) -> Self {
+        Self {
+            correlation_id,
+            response_code,
+            streams,
+        }
+    }
+
+    /// Get a reference to the partitions response's code.
+    pub fn code(&self) -> &ResponseCode {
+        &self.response_code
+    }
+
+    /// Partitions of the super stream
+    pub fn from_response(self) -> Vec<String> {
+        self.streams
+    }
+}
+
+impl Encoder for PartitionsResponse {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size()
+            + self.response_code.encoded_size()
+            + self.streams.encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.response_code.encode(writer)?;
+        self.streams.encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for PartitionsResponse {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, response_code) = ResponseCode::decode(input)?;
+        let (input, streams) = Vec::<String>::decode(input)?;
+
+        Ok((
+            input,
+            PartitionsResponse {
+                correlation_id,
+                response_code,
+                streams,
+            },
+        ))
+    }
+}
+
+impl FromResponse for PartitionsResponse {
+    fn from_response(response: crate::Response) -> Option<Self> {
+        match response.kind {
+            crate::ResponseKind::Partitions(partitions) => Some(partitions),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::tests::command_encode_decode_test;
+
+    use super::{PartitionsRequest, PartitionsResponse};
+
+    #[test]
+    fn partitions_request_test() {
+        command_encode_decode_test::<PartitionsRequest>();
+    }
+
+    #[test]
+    fn partitions_response_test() {
+        command_encode_decode_test::<PartitionsResponse>();
+    }
+}
@@ -8,22 +8,32 @@ use crate::{
 
 use super::Command;
 
-use crate::types::PublishedMessage;
-#[cfg(test)]
-use fake::Fake;
-
+use crate::types::{PublishedEntry, PublishedMessage, PublishedSubEntry};
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct PublishCommand {
     publisher_id: u8,
-    published_messages: Vec<PublishedMessage>,
+    published_messages: Vec<PublishedEntry>,
 }
 
 impl PublishCommand {
     pub fn new(publisher_id: u8, published_messages: Vec<PublishedMessage>) -> Self {
         Self {
             publisher_id,
-            published_messages,
+            published_messages: published_messages
+                .into_iter()
+                .map(PublishedEntry::Simple)
+                .collect(),
+        }
+    }
+
+    pub fn sub_entries(publisher_id: u8, sub_entries: Vec<PublishedSubEntry>) -> Self {
+        Self {
+            publisher_id,
+            published_messages: sub_entries
+                .into_iter()
+                .map(PublishedEntry::SubEntry)
+                .collect(),
         }
     }
 }
@@ -8,9 +8,6 @@ use std::io::Write;
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct QueryOffsetRequest {
@@ -102,6 +99,14 @@ impl QueryOffsetResponse {
     pub fn from_response(&self) -> u64 {
         self.offset
     }
+
+    pub fn code(&self) -> &ResponseCode {
+        &self.response_code
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.response_code == ResponseCode::Ok
+    }
 }
 
 impl Encoder for QueryOffsetResponse {
@@ -0,0 +1,152 @@
+use std::io::Write;
+
+use crate::{
+    codec::{Decoder, Encoder},
+    error::{DecodeError, EncodeError},
+    protocol::commands::COMMAND_ROUTE,
+    FromResponse, ResponseCode,
+};
+
+use super::Command;
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct RouteRequest {
+    correlation_id: u32,
+    routing_key: String,
+    super_stream: String,
+}
+
+impl RouteRequest {
+    pub fn new(correlation_id: u32, routing_key: String, super_stream: String) -> Self {
+        Self {
+            correlation_id,
+            routing_key,
+            super_stream,
+        }
+    }
+}
+
+impl Encoder for RouteRequest {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size()
+            + self.routing_key.as_str().encoded_size()
+            + self.super_stream.as_str().encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.routing_key.as_str().encode(writer)?;
+        self.super_stream.as_str().encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Command for RouteRequest {
+    fn key(&self) -> u16 {
+        COMMAND_ROUTE
+    }
+}
+
+impl Decoder for RouteRequest {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, routing_key) = Option::decode(input)?;
+        let (input, super_stream) = Option::decode(input)?;
+
+        Ok((
+            input,
+            RouteRequest {
+                correlation_id,
+                routing_key: routing_key.unwrap(),
+                super_stream: super_stream.unwrap(),
+            },
+        ))
+    }
+}
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct RouteResponse {
+    pub correlation_id: u32,
+    response_code: ResponseCode,
+    streams: Vec<String>,
+}
+
+impl RouteResponse {
+    pub fn new(correlation_id: u32, response_code: ResponseCode, streams: Vec<String>) -> Self {
+        Self {
+            correlation_id,
+            response_code,
+            streams,
+        }
+    }
+
+    /// Get a reference to the route response's code.
+    pub fn code(&self) -> &ResponseCode {
+        &self.response_code
+    }
+
+    /// Streams bound to the routing key
+    pub fn from_response(self) -> Vec<String> {
+        self.streams
+    }
+}
+
+impl Encoder for RouteResponse {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size()
+            + self.response_code.encoded_size()
+            + self.streams.encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.response_code.encode(writer)?;
+        self.streams.encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for RouteResponse {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, response_code) = ResponseCode::decode(input)?;
+        let (input, streams) = Vec::<String>::decode(input)?;
+
+        Ok((
+            input,
+            RouteResponse {
+                correlation_id,
+                response_code,
+                streams,
+            },
+        ))
+    }
+}
+
+impl FromResponse for RouteResponse {
+    fn from_response(response: crate::Response) -> Option<Self> {
+        match response.kind {
+            crate::ResponseKind::Route(route) => Some(route),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::tests::command_encode_decode_test;
+
+    use super::{RouteRequest, RouteResponse};
+
+    #[test]
+    fn route_request_test() {
+        command_encode_decode_test::<RouteRequest>();
+    }
+
+    #[test]
+    fn route_response_test() {
+        command_encode_decode_test::<RouteResponse>();
+    }
+}
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, io::Write};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::{
+    codec::{decoder::read_u32, Decoder, Encoder},
+    error::{DecodeError, EncodeError},
+    protocol::commands::COMMAND_STREAM_STATS,
+    FromResponse, ResponseCode,
+};
+
+use super::Command;
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct StreamStatsRequest {
+    correlation_id: u32,
+    stream: String,
+}
+
+impl StreamStatsRequest {
+    pub fn new(correlation_id: u32, stream: String) -> Self {
+        Self {
+            correlation_id,
+            stream,
+        }
+    }
+}
+
+impl Encoder for StreamStatsRequest {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size() + self.stream.as_str().encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.stream.as_str().encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Command for StreamStatsRequest {
+    fn key(&self) -> u16 {
+        COMMAND_STREAM_STATS
+    }
+}
+
+impl Decoder for StreamStatsRequest {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, stream) = Option::decode(input)?;
+
+        Ok((
+            input,
+            StreamStatsRequest {
+                correlation_id,
+                stream: stream.unwrap(),
+            },
+        ))
+    }
+}
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct StreamStatsResponse {
+    pub correlation_id: u32,
+    response_code: ResponseCode,
+    stats: HashMap<String, i64>,
+}
+
+impl StreamStatsResponse {
+    pub fn new(
+        correlation_id: u32,
+        response_code: ResponseCode,
+        stats: HashMap<String, i64>,
+    ) -> Self {
+        Self {
+            correlation_id,
+            response_code,
+            stats,
+        }
+    }
+
+    /// Get a reference to the stream stats response's code.
+    pub fn code(&self) -> &ResponseCode {
+        &self.response_code
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.response_code == ResponseCode::Ok
+    }
+
+    /// Statistics of the stream, e.g. `first_chunk_id` and `committed_chunk_id`
+    pub fn from_response(self) -> HashMap<String, i64> {
+        self.stats
+    }
+}
+
+impl Encoder for StreamStatsResponse {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size()
+            + self.response_code.encoded_size()
+            + 4
+            + self.stats.iter().fold(0, |acc, (key, value)| {
+                acc + key.as_str().encoded_size() + value.encoded_size()
+            })
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.response_code.encode(writer)?;
+        writer.write_u32::<BigEndian>(self.stats.len() as u32)?;
+        for (key, value) in &self.stats {
+            key.as_str().encode(writer)?;
+            value.encode(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Decoder for StreamStatsResponse {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, response_code) = ResponseCode::decode(input)?;
+        let (mut input, num_stats) = read_u32(input)?;
+
+        let mut stats = HashMap::with_capacity(num_stats as usize);
+        for _ in 0..num_stats {
+            let (rest, key) = Option::<String>::decode(input)?;
+            let (rest, value) = i64::decode(rest)?;
+            if let Some(key) = key {
+                stats.insert(key, value);
+            }
+            input = rest;
+        }
+
+        Ok((
+            input,
+            StreamStatsResponse {
+                correlation_id,
+                response_code,
+                stats,
+            },
+        ))
+    }
+}
+
+impl FromResponse for StreamStatsResponse {
+    fn from_response(response: crate::Response) -> Option<Self> {
+        match response.kind {
+            crate::ResponseKind::StreamStats(stats) => Some(stats),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::tests::command_encode_decode_test;
+
+    use super::{StreamStatsRequest, StreamStatsResponse};
+
+    #[test]
+    fn stream_stats_request_test() {
+        command_encode_decode_test::<StreamStatsRequest>();
+    }
+
+    #[test]
+    fn stream_stats_response_test() {
+        command_encode_decode_test::<StreamStatsResponse>();
+    }
+}
@@ -1,9 +1,6 @@
 use std::collections::HashMap;
 use std::io::Write;
 
-#[cfg(test)]
-use fake::Fake;
-
 use crate::{
     codec::{Decoder, Encoder},
     error::{DecodeError, EncodeError},
@@ -94,7 +91,7 @@ impl Command for SubscribeCommand {
 }
 
 #[cfg_attr(test, derive(fake::Dummy))]
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum OffsetSpecification {
     First,
     Last,
@@ -0,0 +1,145 @@
+use std::io::{Read, Write};
+
+use crate::error::{DecodeError, EncodeError};
+
+/// Largest buffer allocated upfront for the decompressed data of a sub-entry
+const MAX_PREALLOCATED_SIZE: usize = 1024 * 1024;
+
+/// Compression codec of a sub-entry, the codes are shared with the other RabbitMQ stream clients
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    pub fn code(&self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Snappy => 2,
+            Compression::Lz4 => 3,
+            Compression::Zstd => 4,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Result<Compression, DecodeError> {
+        match code {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Gzip),
+            2 => Ok(Compression::Snappy),
+            3 => Ok(Compression::Lz4),
+            4 => Ok(Compression::Zstd),
+            code => Err(DecodeError::UnsupportedCompression(code)),
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, EncodeError> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            Compression::Snappy => {
+                let mut encoder = snap::write::FrameEncoder::new(Vec::new());
+                encoder.write_all(data)?;
+                encoder
+                    .into_inner()
+                    .map_err(|err| EncodeError::Compression(err.to_string()))
+            }
+            Compression::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+                encoder.write_all(data)?;
+                encoder
+                    .finish()
+                    .map_err(|err| EncodeError::Compression(err.to_string()))
+            }
+            Compression::Zstd => Ok(zstd::encode_all(data, 0)?),
+        }
+    }
+
+    /// Decompress `data`, failing if it inflates to more than the declared `uncompressed_size`
+    pub fn decompress(
+        &self,
+        data: &[u8],
+        uncompressed_size: usize,
+    ) -> Result<Vec<u8>, DecodeError> {
+        // the declared size comes from the wire, do not trust it for the allocation
+        let mut buffer = Vec::with_capacity(uncompressed_size.min(MAX_PREALLOCATED_SIZE));
+        // one more byte than declared is enough to detect an oversized output
+        let limit = uncompressed_size as u64 + 1;
+        let result = match self {
+            Compression::None => data.take(limit).read_to_end(&mut buffer),
+            Compression::Gzip => flate2::read::GzDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut buffer),
+            Compression::Snappy => snap::read::FrameDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut buffer),
+            Compression::Lz4 => lz4_flex::frame::FrameDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut buffer),
+            Compression::Zstd => zstd::stream::read::Decoder::new(data)
+                .and_then(|decoder| decoder.take(limit).read_to_end(&mut buffer)),
+        };
+        result.map_err(|err| DecodeError::Decompression(err.to_string()))?;
+        if buffer.len() > uncompressed_size {
+            return Err(DecodeError::Decompression(format!(
+                "Decompressed data exceeds the declared size of {} bytes",
+                uncompressed_size
+            )));
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+
+    #[test]
+    fn compression_roundtrip_test() {
+        let data = b"message message message message message".repeat(10);
+
+        for compression in [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Snappy,
+            Compression::Lz4,
+            Compression::Zstd,
+        ] {
+            let compressed = compression.compress(&data).unwrap();
+            let decompressed = compression.decompress(&compressed, data.len()).unwrap();
+
+            assert_eq!(data, decompressed);
+            assert_eq!(
+                compression,
+                Compression::from_code(compression.code()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn decompress_over_declared_size_test() {
+        let data = b"message message message message message".repeat(10);
+
+        for compression in [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Snappy,
+            Compression::Lz4,
+            Compression::Zstd,
+        ] {
+            let compressed = compression.compress(&data).unwrap();
+
+            assert!(compression.decompress(&compressed, data.len() - 1).is_err());
+        }
+    }
+}
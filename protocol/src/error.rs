@@ -8,6 +8,8 @@ pub enum DecodeError {
     UnsupportedResponseType(u16),
     MismatchSize(usize),
     MessageParse(String),
+    UnsupportedCompression(u8),
+    Decompression(String),
     Empty,
 }
 
@@ -15,6 +17,7 @@ pub enum DecodeError {
 pub enum EncodeError {
     Io(std::io::Error),
     MaxSizeError(usize),
+    Compression(String),
 }
 
 impl From<std::io::Error> for EncodeError {
@@ -1,5 +1,6 @@
 pub mod codec;
 pub mod commands;
+pub mod compression;
 pub mod error;
 pub mod message;
 mod protocol;
@@ -25,6 +25,12 @@ pub mod commands {
     pub const COMMAND_OPEN: u16 = 21;
     pub const COMMAND_CLOSE: u16 = 22;
     pub const COMMAND_HEARTBEAT: u16 = 23;
+    pub const COMMAND_ROUTE: u16 = 24;
+    pub const COMMAND_PARTITIONS: u16 = 25;
+    pub const COMMAND_CONSUMER_UPDATE: u16 = 26;
+    pub const COMMAND_STREAM_STATS: u16 = 28;
+    pub const COMMAND_CREATE_SUPER_STREAM: u16 = 29;
+    pub const COMMAND_DELETE_SUPER_STREAM: u16 = 30;
 }
 
 // server responses
@@ -49,6 +55,7 @@ pub mod responses {
     pub const RESPONSE_CODE_ACCESS_REFUSED: u16 = 16;
     pub const RESPONSE_CODE_PRECONDITION_FAILED: u16 = 17;
     pub const RESPONSE_CODE_PUBLISHER_DOES_NOT_EXIST: u16 = 18;
+    pub const RESPONSE_CODE_NO_OFFSET: u16 = 19;
 }
 
 // protocol version between client and server
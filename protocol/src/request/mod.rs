@@ -1,16 +1,36 @@
 use std::io::Write;
 
 use crate::{
-    codec::{decoder::read_u32, Decoder, Encoder},
+    codec::{
+        decoder::{extract_response_code, read_u32},
+        encoder::encode_response_code,
+        Decoder, Encoder,
+    },
     commands::{
-        close::CloseRequest, create_stream::CreateStreamCommand, credit::CreditCommand,
-        declare_publisher::DeclarePublisherCommand, delete::Delete,
-        delete_publisher::DeletePublisherCommand, heart_beat::HeartBeatCommand,
-        metadata::MetadataCommand, open::OpenCommand, peer_properties::PeerPropertiesCommand,
-        publish::PublishCommand, query_offset::QueryOffsetRequest,
+        close::{CloseRequest, CloseResponse},
+        consumer_update::ConsumerUpdateResponse,
+        create_stream::CreateStreamCommand,
+        create_super_stream::CreateSuperStreamCommand,
+        credit::CreditCommand,
+        declare_publisher::DeclarePublisherCommand,
+        delete::Delete,
+        delete_publisher::DeletePublisherCommand,
+        delete_super_stream::DeleteSuperStreamCommand,
+        heart_beat::HeartBeatCommand,
+        metadata::MetadataCommand,
+        open::OpenCommand,
+        partitions::PartitionsRequest,
+        peer_properties::PeerPropertiesCommand,
+        publish::PublishCommand,
+        query_offset::QueryOffsetRequest,
         query_publisher_sequence::QueryPublisherRequest,
-        sasl_authenticate::SaslAuthenticateCommand, sasl_handshake::SaslHandshakeCommand,
-        store_offset::StoreOffset, subscribe::SubscribeCommand, tune::TunesCommand,
+        route::RouteRequest,
+        sasl_authenticate::SaslAuthenticateCommand,
+        sasl_handshake::SaslHandshakeCommand,
+        store_offset::StoreOffset,
+        stream_stats::StreamStatsRequest,
+        subscribe::SubscribeCommand,
+        tune::TunesCommand,
         unsubscribe::UnSubscribeCommand,
     },
     error::{DecodeError, EncodeError},
@@ -58,6 +78,13 @@ pub enum RequestKind {
     QueryPublisherSequence(QueryPublisherRequest),
     StoreOffset(StoreOffset),
     Unsubscribe(UnSubscribeCommand),
+    Route(RouteRequest),
+    Partitions(PartitionsRequest),
+    StreamStats(StreamStatsRequest),
+    CreateSuperStream(CreateSuperStreamCommand),
+    DeleteSuperStream(DeleteSuperStreamCommand),
+    ConsumerUpdateResponse(ConsumerUpdateResponse),
+    CloseResponse(CloseResponse),
 }
 
 impl Encoder for RequestKind {
@@ -82,6 +109,17 @@ impl Encoder for RequestKind {
             RequestKind::QueryPublisherSequence(query_publisher) => query_publisher.encoded_size(),
             RequestKind::StoreOffset(store_offset) => store_offset.encoded_size(),
             RequestKind::Unsubscribe(unsubscribe) => unsubscribe.encoded_size(),
+            RequestKind::Route(route) => route.encoded_size(),
+            RequestKind::Partitions(partitions) => partitions.encoded_size(),
+            RequestKind::StreamStats(stream_stats) => stream_stats.encoded_size(),
+            RequestKind::CreateSuperStream(create_super_stream) => {
+                create_super_stream.encoded_size()
+            }
+            RequestKind::DeleteSuperStream(delete_super_stream) => {
+                delete_super_stream.encoded_size()
+            }
+            RequestKind::ConsumerUpdateResponse(consumer_update) => consumer_update.encoded_size(),
+            RequestKind::CloseResponse(close) => close.encoded_size(),
         }
     }
 
@@ -106,6 +144,17 @@ impl Encoder for RequestKind {
             RequestKind::QueryPublisherSequence(query_publisher) => query_publisher.encode(writer),
             RequestKind::StoreOffset(store_offset) => store_offset.encode(writer),
             RequestKind::Unsubscribe(unsubcribe) => unsubcribe.encode(writer),
+            RequestKind::Route(route) => route.encode(writer),
+            RequestKind::Partitions(partitions) => partitions.encode(writer),
+            RequestKind::StreamStats(stream_stats) => stream_stats.encode(writer),
+            RequestKind::CreateSuperStream(create_super_stream) => {
+                create_super_stream.encode(writer)
+            }
+            RequestKind::DeleteSuperStream(delete_super_stream) => {
+                delete_super_stream.encode(writer)
+            }
+            RequestKind::ConsumerUpdateResponse(consumer_update) => consumer_update.encode(writer),
+            RequestKind::CloseResponse(close) => close.encode(writer),
         }
     }
 }
@@ -127,7 +176,7 @@ impl Decoder for Request {
         let (input, _) = read_u32(input)?;
         let (input, header) = Header::decode(input)?;
 
-        let (input, cmd) = match header.key() {
+        let (input, cmd) = match extract_response_code(header.key()) {
             COMMAND_OPEN => OpenCommand::decode(input).map(|(i, kind)| (i, kind.into()))?,
             COMMAND_PEER_PROPERTIES => {
                 PeerPropertiesCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
@@ -144,6 +193,10 @@ impl Decoder for Request {
                 CreateStreamCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
             }
             COMMAND_METADATA => MetadataCommand::decode(input).map(|(i, kind)| (i, kind.into()))?,
+            // the reply of the client to a close initiated by the server
+            COMMAND_CLOSE if header.key() == encode_response_code(COMMAND_CLOSE) => {
+                CloseResponse::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
             COMMAND_CLOSE => CloseRequest::decode(input).map(|(i, kind)| (i, kind.into()))?,
             COMMAND_CREDIT => CreditCommand::decode(input).map(|(i, kind)| (i, kind.into()))?,
             COMMAND_DECLARE_PUBLISHER => {
@@ -171,6 +224,22 @@ impl Decoder for Request {
             COMMAND_UNSUBSCRIBE => {
                 UnSubscribeCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
             }
+            COMMAND_ROUTE => RouteRequest::decode(input).map(|(i, kind)| (i, kind.into()))?,
+            COMMAND_PARTITIONS => {
+                PartitionsRequest::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
+            COMMAND_STREAM_STATS => {
+                StreamStatsRequest::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
+            COMMAND_CREATE_SUPER_STREAM => {
+                CreateSuperStreamCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
+            COMMAND_DELETE_SUPER_STREAM => {
+                DeleteSuperStreamCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
+            COMMAND_CONSUMER_UPDATE => {
+                ConsumerUpdateResponse::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
             n => return Err(DecodeError::UnsupportedResponseType(n)),
         };
         Ok((input, Request { header, kind: cmd }))
@@ -183,15 +252,32 @@ mod tests {
     use crate::{
         codec::{Decoder, Encoder},
         commands::{
-            close::CloseRequest, create_stream::CreateStreamCommand, credit::CreditCommand,
-            declare_publisher::DeclarePublisherCommand, delete::Delete,
-            delete_publisher::DeletePublisherCommand, heart_beat::HeartBeatCommand,
-            metadata::MetadataCommand, open::OpenCommand, peer_properties::PeerPropertiesCommand,
-            publish::PublishCommand, query_offset::QueryOffsetRequest,
+            close::{CloseRequest, CloseResponse},
+            consumer_update::ConsumerUpdateResponse,
+            create_stream::CreateStreamCommand,
+            create_super_stream::CreateSuperStreamCommand,
+            credit::CreditCommand,
+            declare_publisher::DeclarePublisherCommand,
+            delete::Delete,
+            delete_publisher::DeletePublisherCommand,
+            delete_super_stream::DeleteSuperStreamCommand,
+            heart_beat::HeartBeatCommand,
+            metadata::MetadataCommand,
+            open::OpenCommand,
+            partitions::PartitionsRequest,
+            peer_properties::PeerPropertiesCommand,
+            publish::PublishCommand,
+            query_offset::QueryOffsetRequest,
             query_publisher_sequence::QueryPublisherRequest,
-            sasl_authenticate::SaslAuthenticateCommand, sasl_handshake::SaslHandshakeCommand,
-            store_offset::StoreOffset, subscribe::SubscribeCommand, tune::TunesCommand,
-            unsubscribe::UnSubscribeCommand, Command,
+            route::RouteRequest,
+            sasl_authenticate::SaslAuthenticateCommand,
+            sasl_handshake::SaslHandshakeCommand,
+            store_offset::StoreOffset,
+            stream_stats::StreamStatsRequest,
+            subscribe::SubscribeCommand,
+            tune::TunesCommand,
+            unsubscribe::UnSubscribeCommand,
+            Command,
         },
     };
 
@@ -288,6 +374,41 @@ mod tests {
     fn request_unsubscribe_test() {
         request_encode_decode_test::<UnSubscribeCommand>()
     }
+
+    #[test]
+    fn request_route_test() {
+        request_encode_decode_test::<RouteRequest>()
+    }
+
+    #[test]
+    fn request_partitions_test() {
+        request_encode_decode_test::<PartitionsRequest>()
+    }
+
+    #[test]
+    fn request_stream_stats_test() {
+        request_encode_decode_test::<StreamStatsRequest>()
+    }
+
+    #[test]
+    fn request_create_super_stream_test() {
+        request_encode_decode_test::<CreateSuperStreamCommand>()
+    }
+
+    #[test]
+    fn request_delete_super_stream_test() {
+        request_encode_decode_test::<DeleteSuperStreamCommand>()
+    }
+
+    #[test]
+    fn request_consumer_update_response_test() {
+        request_encode_decode_test::<ConsumerUpdateResponse>()
+    }
+
+    #[test]
+    fn request_close_response_test() {
+        request_encode_decode_test::<CloseResponse>()
+    }
     fn request_encode_decode_test<T>()
     where
         T: Dummy<Faker> + Encoder + Decoder + Debug + PartialEq + Command + Into<Request>,
@@ -297,7 +418,7 @@ mod tests {
         let request: Request = command.into();
 
         let mut buffer = vec![];
-        let _ = request.encode(&mut buffer).unwrap();
+        request.encode(&mut buffer).unwrap();
 
         let (remaining, decoded) = Request::decode(&buffer).unwrap();
 
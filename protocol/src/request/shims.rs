@@ -1,14 +1,31 @@
 use crate::{
     commands::{
-        close::CloseRequest, create_stream::CreateStreamCommand, credit::CreditCommand,
-        declare_publisher::DeclarePublisherCommand, delete::Delete,
-        delete_publisher::DeletePublisherCommand, heart_beat::HeartBeatCommand,
-        metadata::MetadataCommand, open::OpenCommand, peer_properties::PeerPropertiesCommand,
-        publish::PublishCommand, query_offset::QueryOffsetRequest,
+        close::{CloseRequest, CloseResponse},
+        consumer_update::ConsumerUpdateResponse,
+        create_stream::CreateStreamCommand,
+        create_super_stream::CreateSuperStreamCommand,
+        credit::CreditCommand,
+        declare_publisher::DeclarePublisherCommand,
+        delete::Delete,
+        delete_publisher::DeletePublisherCommand,
+        delete_super_stream::DeleteSuperStreamCommand,
+        heart_beat::HeartBeatCommand,
+        metadata::MetadataCommand,
+        open::OpenCommand,
+        partitions::PartitionsRequest,
+        peer_properties::PeerPropertiesCommand,
+        publish::PublishCommand,
+        query_offset::QueryOffsetRequest,
         query_publisher_sequence::QueryPublisherRequest,
-        sasl_authenticate::SaslAuthenticateCommand, sasl_handshake::SaslHandshakeCommand,
-        store_offset::StoreOffset, subscribe::SubscribeCommand, tune::TunesCommand,
-        unsubscribe::UnSubscribeCommand, Command,
+        route::RouteRequest,
+        sasl_authenticate::SaslAuthenticateCommand,
+        sasl_handshake::SaslHandshakeCommand,
+        store_offset::StoreOffset,
+        stream_stats::StreamStatsRequest,
+        subscribe::SubscribeCommand,
+        tune::TunesCommand,
+        unsubscribe::UnSubscribeCommand,
+        Command,
     },
     protocol::version::PROTOCOL_VERSION,
     types::Header,
@@ -130,3 +147,45 @@ impl From<UnSubscribeCommand> for RequestKind {
         RequestKind::Unsubscribe(cmd)
     }
 }
+
+impl From<RouteRequest> for RequestKind {
+    fn from(cmd: RouteRequest) -> Self {
+        RequestKind::Route(cmd)
+    }
+}
+
+impl From<PartitionsRequest> for RequestKind {
+    fn from(cmd: PartitionsRequest) -> Self {
+        RequestKind::Partitions(cmd)
+    }
+}
+
+impl From<StreamStatsRequest> for RequestKind {
+    fn from(cmd: StreamStatsRequest) -> Self {
+        RequestKind::StreamStats(cmd)
+    }
+}
+
+impl From<CreateSuperStreamCommand> for RequestKind {
+    fn from(cmd: CreateSuperStreamCommand) -> Self {
+        RequestKind::CreateSuperStream(cmd)
+    }
+}
+
+impl From<DeleteSuperStreamCommand> for RequestKind {
+    fn from(cmd: DeleteSuperStreamCommand) -> Self {
+        RequestKind::DeleteSuperStream(cmd)
+    }
+}
+
+impl From<ConsumerUpdateResponse> for RequestKind {
+    fn from(cmd: ConsumerUpdateResponse) -> Self {
+        RequestKind::ConsumerUpdateResponse(cmd)
+    }
+}
+
+impl From<CloseResponse> for RequestKind {
+    fn from(cmd: CloseResponse) -> Self {
+        RequestKind::CloseResponse(cmd)
+    }
+}
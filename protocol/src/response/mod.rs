@@ -2,16 +2,29 @@ use std::convert::TryInto;
 
 use crate::{
     codec::{
-        decoder::{read_u16, read_u32},
+        decoder::{extract_response_code, read_u16, read_u32},
+        encoder::encode_response_code,
         Decoder,
     },
     commands::{
-        close::CloseResponse, credit::CreditResponse, deliver::DeliverCommand,
-        generic::GenericResponse, heart_beat::HeartbeatResponse, metadata::MetadataResponse,
-        metadata_update::MetadataUpdateCommand, open::OpenResponse,
-        peer_properties::PeerPropertiesResponse, publish_confirm::PublishConfirm,
-        publish_error::PublishErrorResponse, query_offset::QueryOffsetResponse,
-        query_publisher_sequence::QueryPublisherResponse, sasl_handshake::SaslHandshakeResponse,
+        close::{CloseRequest, CloseResponse},
+        consumer_update::ConsumerUpdateCommand,
+        credit::CreditResponse,
+        deliver::DeliverCommand,
+        generic::GenericResponse,
+        heart_beat::HeartbeatResponse,
+        metadata::MetadataResponse,
+        metadata_update::MetadataUpdateCommand,
+        open::OpenResponse,
+        partitions::PartitionsResponse,
+        peer_properties::PeerPropertiesResponse,
+        publish_confirm::PublishConfirm,
+        publish_error::PublishErrorResponse,
+        query_offset::QueryOffsetResponse,
+        query_publisher_sequence::QueryPublisherResponse,
+        route::RouteResponse,
+        sasl_handshake::SaslHandshakeResponse,
+        stream_stats::StreamStatsResponse,
         tune::TunesCommand,
     },
     error::DecodeError,
@@ -41,6 +54,7 @@ pub enum ResponseCode {
     AccessRefused,
     PrecoditionFailed,
     PublisherDoesNotExist,
+    NoOffset,
 }
 #[derive(Debug, PartialEq)]
 pub struct Response {
@@ -52,6 +66,7 @@ pub struct Response {
 pub enum ResponseKind {
     Open(OpenResponse),
     Close(CloseResponse),
+    CloseRequest(CloseRequest),
     PeerProperties(PeerPropertiesResponse),
     SaslHandshake(SaslHandshakeResponse),
     Generic(GenericResponse),
@@ -65,6 +80,10 @@ pub enum ResponseKind {
     QueryOffset(QueryOffsetResponse),
     QueryPublisherSequence(QueryPublisherResponse),
     Credit(CreditResponse),
+    Route(RouteResponse),
+    Partitions(PartitionsResponse),
+    StreamStats(StreamStatsResponse),
+    ConsumerUpdate(ConsumerUpdateCommand),
 }
 
 impl Response {
@@ -84,6 +103,12 @@ impl Response {
             ResponseKind::QueryPublisherSequence(query_publisher) => {
                 Some(query_publisher.correlation_id)
             }
+            ResponseKind::Route(route) => Some(route.correlation_id),
+            ResponseKind::Partitions(partitions) => Some(partitions.correlation_id),
+            ResponseKind::StreamStats(stream_stats) => Some(stream_stats.correlation_id),
+            // sent by the server, the correlation id is used for the reply of the client
+            ResponseKind::ConsumerUpdate(_) => None,
+            ResponseKind::CloseRequest(_) => None,
             ResponseKind::MetadataUpdate(_) => None,
             ResponseKind::PublishConfirm(_) => None,
             ResponseKind::PublishError(_) => None,
@@ -101,6 +126,10 @@ impl Response {
         T::from_response(self)
     }
 
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
     pub fn kind_ref(&self) -> &ResponseKind {
         &self.kind
     }
@@ -115,14 +144,17 @@ impl Decoder for Response {
 
         let (input, header) = Header::decode(input)?;
 
-        let (input, kind) = match header.key() {
+        let (input, kind) = match extract_response_code(header.key()) {
             COMMAND_OPEN => {
                 OpenResponse::decode(input).map(|(i, kind)| (i, ResponseKind::Open(kind)))?
             }
 
-            COMMAND_CLOSE => {
+            COMMAND_CLOSE if header.key() == encode_response_code(COMMAND_CLOSE) => {
                 CloseResponse::decode(input).map(|(i, kind)| (i, ResponseKind::Close(kind)))?
             }
+            // the server is closing the connection
+            COMMAND_CLOSE => CloseRequest::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::CloseRequest(kind)))?,
             COMMAND_PEER_PROPERTIES => PeerPropertiesResponse::decode(input)
                 .map(|(i, kind)| (i, ResponseKind::PeerProperties(kind)))?,
             COMMAND_SASL_HANDSHAKE => SaslHandshakeResponse::decode(input)
@@ -134,7 +166,9 @@ impl Decoder for Response {
             | COMMAND_SUBSCRIBE
             | COMMAND_UNSUBSCRIBE
             | COMMAND_CREATE_STREAM
-            | COMMAND_DELETE_STREAM => {
+            | COMMAND_DELETE_STREAM
+            | COMMAND_CREATE_SUPER_STREAM
+            | COMMAND_DELETE_SUPER_STREAM => {
                 GenericResponse::decode(input).map(|(i, kind)| (i, ResponseKind::Generic(kind)))?
             }
             COMMAND_TUNE => {
@@ -164,6 +198,18 @@ impl Decoder for Response {
             COMMAND_QUERY_PUBLISHER_SEQUENCE => QueryPublisherResponse::decode(input)
                 .map(|(remaining, kind)| (remaining, ResponseKind::QueryPublisherSequence(kind)))?,
 
+            COMMAND_ROUTE => RouteResponse::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::Route(kind)))?,
+
+            COMMAND_PARTITIONS => PartitionsResponse::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::Partitions(kind)))?,
+
+            COMMAND_STREAM_STATS => StreamStatsResponse::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::StreamStats(kind)))?,
+
+            COMMAND_CONSUMER_UPDATE => ConsumerUpdateCommand::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::ConsumerUpdate(kind)))?,
+
             n => return Err(DecodeError::UnsupportedResponseType(n)),
         };
         Ok((input, Response { header, kind }))
@@ -191,23 +237,35 @@ mod tests {
     use byteorder::{BigEndian, WriteBytesExt};
 
     use crate::{
-        codec::{Decoder, Encoder},
+        codec::{encoder::encode_response_code, Decoder, Encoder},
         commands::{
-            close::CloseResponse, deliver::DeliverCommand, generic::GenericResponse,
-            heart_beat::HeartbeatResponse, metadata::MetadataResponse,
-            metadata_update::MetadataUpdateCommand, open::OpenResponse,
-            peer_properties::PeerPropertiesResponse, publish_confirm::PublishConfirm,
-            publish_error::PublishErrorResponse, query_offset::QueryOffsetResponse,
+            close::{CloseRequest, CloseResponse},
+            consumer_update::ConsumerUpdateCommand,
+            deliver::DeliverCommand,
+            generic::GenericResponse,
+            heart_beat::HeartbeatResponse,
+            metadata::MetadataResponse,
+            metadata_update::MetadataUpdateCommand,
+            open::OpenResponse,
+            partitions::PartitionsResponse,
+            peer_properties::PeerPropertiesResponse,
+            publish_confirm::PublishConfirm,
+            publish_error::PublishErrorResponse,
+            query_offset::QueryOffsetResponse,
             query_publisher_sequence::QueryPublisherResponse,
-            sasl_handshake::SaslHandshakeResponse, tune::TunesCommand,
+            route::RouteResponse,
+            sasl_handshake::SaslHandshakeResponse,
+            stream_stats::StreamStatsResponse,
+            tune::TunesCommand,
         },
         protocol::{
             commands::{
-                COMMAND_CLOSE, COMMAND_DELIVER, COMMAND_HEARTBEAT, COMMAND_METADATA,
-                COMMAND_METADATA_UPDATE, COMMAND_OPEN, COMMAND_PEER_PROPERTIES,
-                COMMAND_PUBLISH_CONFIRM, COMMAND_PUBLISH_ERROR, COMMAND_QUERY_OFFSET,
-                COMMAND_QUERY_PUBLISHER_SEQUENCE, COMMAND_SASL_AUTHENTICATE,
-                COMMAND_SASL_HANDSHAKE, COMMAND_TUNE,
+                COMMAND_CLOSE, COMMAND_CONSUMER_UPDATE, COMMAND_DELIVER, COMMAND_HEARTBEAT,
+                COMMAND_METADATA, COMMAND_METADATA_UPDATE, COMMAND_OPEN, COMMAND_PARTITIONS,
+                COMMAND_PEER_PROPERTIES, COMMAND_PUBLISH_CONFIRM, COMMAND_PUBLISH_ERROR,
+                COMMAND_QUERY_OFFSET, COMMAND_QUERY_PUBLISHER_SEQUENCE, COMMAND_ROUTE,
+                COMMAND_SASL_AUTHENTICATE, COMMAND_SASL_HANDSHAKE, COMMAND_STREAM_STATS,
+                COMMAND_TUNE,
             },
             version::PROTOCOL_VERSION,
         },
@@ -220,6 +278,7 @@ mod tests {
             match self {
                 ResponseKind::Open(open) => open.encoded_size(),
                 ResponseKind::Close(close) => close.encoded_size(),
+                ResponseKind::CloseRequest(close) => close.encoded_size(),
                 ResponseKind::PeerProperties(peer_properties) => peer_properties.encoded_size(),
                 ResponseKind::SaslHandshake(handshake) => handshake.encoded_size(),
                 ResponseKind::Generic(generic) => generic.encoded_size(),
@@ -235,6 +294,10 @@ mod tests {
                     query_publisher.encoded_size()
                 }
                 ResponseKind::Credit(credit) => credit.encoded_size(),
+                ResponseKind::Route(route) => route.encoded_size(),
+                ResponseKind::Partitions(partitions) => partitions.encoded_size(),
+                ResponseKind::StreamStats(stream_stats) => stream_stats.encoded_size(),
+                ResponseKind::ConsumerUpdate(consumer_update) => consumer_update.encoded_size(),
             }
         }
 
@@ -245,6 +308,7 @@ mod tests {
             match self {
                 ResponseKind::Open(open) => open.encode(writer),
                 ResponseKind::Close(close) => close.encode(writer),
+                ResponseKind::CloseRequest(close) => close.encode(writer),
                 ResponseKind::PeerProperties(peer_properties) => peer_properties.encode(writer),
                 ResponseKind::SaslHandshake(handshake) => handshake.encode(writer),
                 ResponseKind::Generic(generic) => generic.encode(writer),
@@ -260,6 +324,10 @@ mod tests {
                     query_publisher.encode(writer)
                 }
                 ResponseKind::Credit(credit) => credit.encode(writer),
+                ResponseKind::Route(route) => route.encode(writer),
+                ResponseKind::Partitions(partitions) => partitions.encode(writer),
+                ResponseKind::StreamStats(stream_stats) => stream_stats.encode(writer),
+                ResponseKind::ConsumerUpdate(consumer_update) => consumer_update.encode(writer),
             }
         }
     }
@@ -344,7 +412,15 @@ mod tests {
     }
     #[test]
     fn close_response_test() {
-        response_test!(CloseResponse, ResponseKind::Close, COMMAND_CLOSE);
+        response_test!(
+            CloseResponse,
+            ResponseKind::Close,
+            encode_response_code(COMMAND_CLOSE)
+        );
+    }
+    #[test]
+    fn close_request_response_test() {
+        response_test!(CloseRequest, ResponseKind::CloseRequest, COMMAND_CLOSE);
     }
     #[test]
     fn deliver_response_test() {
@@ -398,4 +474,33 @@ mod tests {
             COMMAND_HEARTBEAT
         );
     }
+
+    #[test]
+    fn route_response_test() {
+        response_test!(RouteResponse, ResponseKind::Route, COMMAND_ROUTE);
+    }
+    #[test]
+    fn partitions_response_test() {
+        response_test!(
+            PartitionsResponse,
+            ResponseKind::Partitions,
+            COMMAND_PARTITIONS
+        );
+    }
+    #[test]
+    fn stream_stats_response_test() {
+        response_test!(
+            StreamStatsResponse,
+            ResponseKind::StreamStats,
+            COMMAND_STREAM_STATS
+        );
+    }
+    #[test]
+    fn consumer_update_response_test() {
+        response_test!(
+            ConsumerUpdateCommand,
+            ResponseKind::ConsumerUpdate,
+            COMMAND_CONSUMER_UPDATE
+        );
+    }
 }
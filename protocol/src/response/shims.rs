@@ -34,6 +34,7 @@ impl TryFrom<u16> for ResponseCode {
             RESPONSE_CODE_ACCESS_REFUSED => Ok(ResponseCode::AccessRefused),
             RESPONSE_CODE_PRECONDITION_FAILED => Ok(ResponseCode::PrecoditionFailed),
             RESPONSE_CODE_PUBLISHER_DOES_NOT_EXIST => Ok(ResponseCode::PublisherDoesNotExist),
+            RESPONSE_CODE_NO_OFFSET => Ok(ResponseCode::NoOffset),
             _ => Err(DecodeError::UnknownResponseCode(value)),
         }
     }
@@ -66,6 +67,7 @@ impl From<&ResponseCode> for u16 {
             ResponseCode::AccessRefused => RESPONSE_CODE_ACCESS_REFUSED,
             ResponseCode::PrecoditionFailed => RESPONSE_CODE_PRECONDITION_FAILED,
             ResponseCode::PublisherDoesNotExist => RESPONSE_CODE_PUBLISHER_DOES_NOT_EXIST,
+            ResponseCode::NoOffset => RESPONSE_CODE_NO_OFFSET,
         }
     }
 }
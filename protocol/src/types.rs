@@ -1,4 +1,4 @@
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Header {
     key: u16,
     version: u16,
@@ -20,10 +20,9 @@ impl Header {
     }
 }
 
-#[cfg(test)]
-use fake::Fake;
-
-use crate::{message::Message, ResponseCode};
+use crate::{
+    codec::Encoder, compression::Compression, error::EncodeError, message::Message, ResponseCode,
+};
 
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(Debug, PartialEq)]
@@ -41,6 +40,47 @@ impl PublishedMessage {
     }
 }
 
+/// Many messages packed and compressed in a single publish entry
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(Debug, PartialEq)]
+pub struct PublishedSubEntry {
+    pub publishing_id: u64,
+    pub compression: Compression,
+    pub records: u16,
+    pub uncompressed_size: u32,
+    pub data: Vec<u8>,
+}
+
+impl PublishedSubEntry {
+    /// Pack `messages` in a sub-entry, the broker deduplicates it using `publishing_id`
+    pub fn new(
+        publishing_id: u64,
+        compression: Compression,
+        messages: &[Message],
+    ) -> Result<Self, EncodeError> {
+        let mut buffer = Vec::new();
+        for message in messages {
+            message.encoded_size().encode(&mut buffer)?;
+            message.encode(&mut buffer)?;
+        }
+
+        Ok(Self {
+            publishing_id,
+            compression,
+            records: messages.len() as u16,
+            uncompressed_size: buffer.len() as u32,
+            data: compression.compress(&buffer)?,
+        })
+    }
+}
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(Debug, PartialEq)]
+pub enum PublishedEntry {
+    Simple(PublishedMessage),
+    SubEntry(PublishedSubEntry),
+}
+
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(Debug, PartialEq)]
 pub struct PublishingError {
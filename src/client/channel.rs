@@ -14,13 +14,13 @@ use crate::error::ClientError;
 
 pub fn channel<S, T>(sink: S, stream: T) -> (ChannelSender<S>, ChannelReceiver<T>)
 where
-    S: Sink<Request>,
+    S: Sink<Request, Error = ClientError>,
     T: Stream<Item = Result<Response, ClientError>>,
 {
     let closed = Arc::new(AtomicBool::new(false));
     (
         ChannelSender {
-            inner: Arc::new(Mutex::new(sink)),
+            inner: Arc::new(Mutex::new(Some(sink))),
             closed: closed.clone(),
         },
         ChannelReceiver {
@@ -33,19 +33,25 @@ where
 #[derive(Clone)]
 pub struct ChannelSender<T>
 where
-    T: Sink<Request>,
+    T: Sink<Request, Error = ClientError>,
 {
-    inner: Arc<Mutex<T>>,
+    /// `None` once the connection has been released
+    inner: Arc<Mutex<Option<T>>>,
     closed: Arc<AtomicBool>,
 }
 
 impl<T> ChannelSender<T>
 where
-    T: Sink<Request>,
+    T: Sink<Request, Error = ClientError>,
 {
     pub fn is_closed(&self) -> bool {
         self.closed.load(Relaxed)
     }
+
+    /// Refuse to close the connection again once the server closed it
+    pub fn set_closed(&self) {
+        self.closed.store(true, Relaxed);
+    }
 }
 
 impl<T> ChannelReceiver<T>
@@ -65,19 +71,34 @@ where
     closed: Arc<AtomicBool>,
 }
 
-impl<T: Sink<Request> + Unpin> ChannelSender<T> {
-    pub async fn send(&self, item: Request) -> Result<(), T::Error> {
-        let mut channel = self.inner.lock().await;
-        channel.send(item).await
+impl<T: Sink<Request, Error = ClientError> + Unpin> ChannelSender<T> {
+    pub async fn send(&self, item: Request) -> Result<(), ClientError> {
+        match self.inner.lock().await.as_mut() {
+            Some(channel) => channel.send(item).await,
+            None => Err(ClientError::ConnectionClosed),
+        }
     }
 
-    pub async fn close(&self) -> Result<(), T::Error> {
+    pub async fn close(&self) -> Result<(), ClientError> {
         let mut channel = self.inner.lock().await;
-        channel.close().await?;
+        channel
+            .as_mut()
+            .ok_or(ClientError::AlreadyClosed)?
+            .close()
+            .await?;
         self.closed.store(true, Relaxed);
 
         Ok(())
     }
+
+    /// Close the sink, ignoring errors, and drop it so that the socket is released
+    /// once the reading side stopped as well
+    pub async fn shutdown(&self) {
+        if let Some(mut channel) = self.inner.lock().await.take() {
+            let _ = channel.close().await;
+        }
+        self.closed.store(true, Relaxed);
+    }
 }
 
 impl<T: Stream<Item = Result<Response, ClientError>> + Unpin> ChannelReceiver<T> {
@@ -1,12 +1,18 @@
 use futures::Stream;
 use rabbitmq_stream_protocol::Response;
-use std::sync::{atomic::AtomicU32, Arc};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
 use tracing::trace;
 
 use dashmap::DashMap;
 use tokio::sync::{
     mpsc::{channel, Receiver, Sender},
-    RwLock,
+    Mutex as AsyncMutex, Notify, RwLock,
 };
 
 use crate::error::ClientError;
@@ -20,6 +26,10 @@ pub struct DispatcherState<T> {
     requests: Arc<DashMap<u32, Sender<Response>>>,
     correlation_id: Arc<AtomicU32>,
     handler: Arc<RwLock<Option<T>>>,
+    last_received: Arc<Mutex<Instant>>,
+    running: Arc<AtomicBool>,
+    shutdown: Arc<Notify>,
+    shutdown_reason: Arc<AsyncMutex<Option<ClientError>>>,
 }
 
 impl<T> Clone for DispatcherState<T> {
@@ -28,6 +38,24 @@ impl<T> Clone for DispatcherState<T> {
             requests: self.requests.clone(),
             correlation_id: self.correlation_id.clone(),
             handler: self.handler.clone(),
+            last_received: self.last_received.clone(),
+            running: self.running.clone(),
+            shutdown: self.shutdown.clone(),
+            shutdown_reason: self.shutdown_reason.clone(),
+        }
+    }
+}
+
+impl<T> DispatcherState<T> {
+    fn with_handler(handler: Option<T>) -> DispatcherState<T> {
+        DispatcherState {
+            requests: Arc::new(DashMap::new()),
+            correlation_id: Arc::new(AtomicU32::new(0)),
+            handler: Arc::new(RwLock::new(handler)),
+            last_received: Arc::new(Mutex::new(Instant::now())),
+            running: Arc::new(AtomicBool::new(false)),
+            shutdown: Arc::new(Notify::new()),
+            shutdown_reason: Arc::new(AsyncMutex::new(None)),
         }
     }
 }
@@ -37,23 +65,15 @@ where
     T: MessageHandler,
 {
     pub fn new() -> Dispatcher<T> {
-        Dispatcher(DispatcherState {
-            requests: Arc::new(DashMap::new()),
-            correlation_id: Arc::new(AtomicU32::new(0)),
-            handler: Arc::new(RwLock::new(None)),
-        })
+        Dispatcher(DispatcherState::with_handler(None))
     }
 
     #[cfg(test)]
     pub fn with_handler(handler: T) -> Dispatcher<T> {
-        Dispatcher(DispatcherState {
-            requests: Arc::new(DashMap::new()),
-            correlation_id: Arc::new(AtomicU32::new(0)),
-            handler: Arc::new(RwLock::new(Some(handler))),
-        })
+        Dispatcher(DispatcherState::with_handler(Some(handler)))
     }
 
-    pub async fn response_channel(&self) -> (u32, Receiver<Response>) {
+    pub async fn response_channel(&self) -> (u32, ResponseReceiver) {
         let (tx, rx) = channel(1);
 
         let correlation_id = self
@@ -63,7 +83,14 @@ where
 
         self.0.requests.insert(correlation_id, tx);
 
-        (correlation_id, rx)
+        (
+            correlation_id,
+            ResponseReceiver {
+                correlation_id,
+                receiver: rx,
+                requests: self.0.requests.clone(),
+            },
+        )
     }
 
     #[cfg(test)]
@@ -80,8 +107,45 @@ where
         R: Stream<Item = Result<Response, ClientError>> + Unpin + Send,
         R: 'static,
     {
+        self.0.running.store(true, Ordering::SeqCst);
         handle_response(self.0.clone(), stream).await
     }
+
+    /// Instant of the last frame received from the server
+    pub fn last_received(&self) -> Instant {
+        *self.0.last_received.lock().unwrap()
+    }
+
+    /// Check if the dispatcher is still reading from the connection
+    pub fn is_running(&self) -> bool {
+        self.0.running.load(Ordering::SeqCst)
+    }
+
+    /// Stop reading from the connection, the handler is notified with `reason`
+    pub async fn shutdown(&self, reason: ClientError) {
+        *self.0.shutdown_reason.lock().await = Some(reason);
+        self.0.shutdown.notify_one();
+    }
+}
+
+/// Receiver of the response to a request, the request is forgotten by the dispatcher
+/// once dropped, even if the response never came
+pub struct ResponseReceiver {
+    correlation_id: u32,
+    receiver: Receiver<Response>,
+    requests: Arc<DashMap<u32, Sender<Response>>>,
+}
+
+impl ResponseReceiver {
+    pub async fn recv(&mut self) -> Option<Response> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for ResponseReceiver {
+    fn drop(&mut self) {
+        self.requests.remove(&self.correlation_id);
+    }
 }
 
 impl<T> DispatcherState<T>
@@ -102,7 +166,14 @@ where
         }
     }
 
+    fn received(&self) {
+        *self.last_received.lock().unwrap() = Instant::now();
+    }
+
     pub async fn close(self, error: Option<ClientError>) {
+        self.running.store(false, Ordering::SeqCst);
+        // no response will come anymore, the requests waiting for one get `None`
+        self.requests.clear();
         if let Some(handler) = self.handler.read().await.as_ref() {
             if let Some(err) = error {
                 let _ = handler.handle_message(Some(Err(err))).await;
@@ -121,23 +192,34 @@ where
     tokio::spawn(async move {
         // TODO implements Error handling and close of dispatcher
         trace!("Dispatcher task: listening for messages");
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(item) => match item.correlation_id() {
-                    Some(correlation_id) => state.dispatch(correlation_id, item).await,
-                    None => state.notify(item).await,
+        let mut error = None;
+        loop {
+            tokio::select! {
+                result = stream.next() => match result {
+                    Some(Ok(item)) => {
+                        state.received();
+                        match item.correlation_id() {
+                            Some(correlation_id) => state.dispatch(correlation_id, item).await,
+                            None => state.notify(item).await,
+                        }
+                    }
+                    Some(Err(e)) => {
+                        trace!("Error from stream {:?}", e);
+                        break;
+                    }
+                    None => break,
                 },
-                Err(e) => {
-                    trace!("Error from stream {:?}", e);
+                _ = state.shutdown.notified() => {
+                    error = state.shutdown_reason.lock().await.take();
+                    trace!("Dispatcher shutdown {:?}", error);
                     break;
                 }
             }
         }
         if !stream.is_closed() {
-            // TODO handle reconnection if the connection is broken
             trace!("Stream closed");
         }
-        state.close(None).await;
+        state.close(error).await;
     });
 }
 
@@ -160,7 +242,7 @@ mod tests {
     };
     use tokio::sync::mpsc::channel as tokio_channel;
 
-    use crate::client::MessageResult;
+    use crate::{client::MessageResult, error::ClientError};
 
     use super::super::channel::channel;
 
@@ -171,6 +253,7 @@ mod tests {
     #[derive(Clone)]
     enum MockIO {
         Push,
+        Idle,
         Request(Arc<Mutex<Option<Request>>>),
     }
 
@@ -179,6 +262,10 @@ mod tests {
             MockIO::Push
         }
 
+        fn idle() -> MockIO {
+            MockIO::Idle
+        }
+
         fn request() -> MockIO {
             MockIO::Request(Arc::new(Mutex::new(None)))
         }
@@ -195,6 +282,7 @@ mod tests {
                     Header::new(23, 1),
                     ResponseKind::Heartbeat(HeartbeatResponse {}),
                 )))),
+                MockIO::Idle => Poll::Pending,
                 MockIO::Request(request) => match request.lock().unwrap().take() {
                     Some(request) => match request.kind() {
                         RequestKind::PeerProperties(peer) => {
@@ -231,7 +319,7 @@ mod tests {
             let this = unsafe { Pin::get_unchecked_mut(self) };
 
             match this {
-                MockIO::Push => todo!(),
+                MockIO::Push | MockIO::Idle => todo!(),
                 MockIO::Request(request) => {
                     let mut guard = request.lock().unwrap();
                     *guard = Some(item);
@@ -251,7 +339,7 @@ mod tests {
             self: Pin<&mut Self>,
             _cx: &mut Context<'_>,
         ) -> Poll<Result<(), Self::Error>> {
-            todo!()
+            Poll::Ready(Ok(()))
         }
     }
 
@@ -298,4 +386,78 @@ mod tests {
 
         assert!(matches!(response, Some(..)));
     }
+
+    #[tokio::test]
+    async fn should_close_on_shutdown() {
+        let mock_source = MockIO::idle();
+
+        let (close_tx, mut close_rx) = tokio_channel(2);
+
+        let handler = move |response: MessageResult| {
+            let close_tx = close_tx.clone();
+            async move {
+                close_tx.send(response).await.unwrap();
+                Ok(())
+            }
+        };
+        let (_tx, rx) = channel(mock_source.clone(), mock_source.clone());
+        let dispatcher = Dispatcher::with_handler(handler);
+
+        dispatcher.start(rx).await;
+        assert!(dispatcher.is_running());
+
+        dispatcher.shutdown(ClientError::HeartbeatTimeout).await;
+
+        assert!(matches!(
+            close_rx.recv().await,
+            Some(Some(Err(ClientError::HeartbeatTimeout)))
+        ));
+        assert!(matches!(close_rx.recv().await, Some(None)));
+        assert!(!dispatcher.is_running());
+    }
+
+    #[tokio::test]
+    async fn should_forget_a_dropped_request() {
+        let handler = |_| async { Ok(()) };
+        let dispatcher = Dispatcher::with_handler(handler);
+
+        let (_, rx) = dispatcher.response_channel().await;
+        assert_eq!(1, dispatcher.requests_count().await);
+
+        drop(rx);
+        assert_eq!(0, dispatcher.requests_count().await);
+    }
+
+    #[tokio::test]
+    async fn should_drop_waiting_requests_on_close() {
+        let mock_source = MockIO::idle();
+
+        let handler = |_| async { Ok(()) };
+        let (_tx, rx) = channel(mock_source.clone(), mock_source.clone());
+        let dispatcher = Dispatcher::with_handler(handler);
+
+        dispatcher.start(rx).await;
+        let (_, mut rx) = dispatcher.response_channel().await;
+
+        dispatcher.shutdown(ClientError::HeartbeatTimeout).await;
+
+        assert!(rx.recv().await.is_none());
+        assert_eq!(0, dispatcher.requests_count().await);
+    }
+
+    #[tokio::test]
+    async fn should_release_the_sink_on_shutdown() {
+        let mock_source = MockIO::request();
+        let (tx, _rx) = channel(mock_source.clone(), mock_source.clone());
+
+        tx.shutdown().await;
+
+        assert!(tx.is_closed());
+        assert!(matches!(
+            tx.send(PeerPropertiesCommand::new(0, HashMap::new()).into())
+                .await,
+            Err(ClientError::ConnectionClosed)
+        ));
+        assert!(matches!(tx.close().await, Err(ClientError::AlreadyClosed)));
+    }
 }
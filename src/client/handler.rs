@@ -1,6 +1,7 @@
 use crate::{error::ClientError, RabbitMQStreamResult};
 use rabbitmq_stream_protocol::Response;
-use std::future::Future;
+use std::{future::Future, sync::Arc};
+use tokio::sync::mpsc;
 
 pub type MessageResult = Option<Result<Response, ClientError>>;
 
@@ -22,3 +23,79 @@ where
         fun(item).await
     }
 }
+
+/// Runs the messages of a handler one after the other, in the order they were received
+#[derive(Clone)]
+pub(crate) struct HandlerQueue(mpsc::UnboundedSender<MessageResult>);
+
+impl HandlerQueue {
+    /// Start running the messages pushed to the queue with `handler`, until the queue is dropped
+    pub(crate) fn new(handler: Arc<dyn MessageHandler>) -> HandlerQueue {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(item) = receiver.recv().await {
+                let _ = handler.handle_message(item).await;
+            }
+        });
+        HandlerQueue(sender)
+    }
+
+    pub(crate) fn push(&self, item: MessageResult) {
+        let _ = self.0.send(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use rabbitmq_stream_protocol::ResponseCode;
+    use tokio::sync::oneshot;
+
+    use super::{HandlerQueue, MessageResult};
+    use crate::error::ClientError;
+
+    #[tokio::test]
+    async fn should_handle_messages_in_order() {
+        let handled = Arc::new(Mutex::new(vec![]));
+        let (done, finished) = oneshot::channel();
+        let done = Arc::new(Mutex::new(Some(done)));
+
+        let queue = HandlerQueue::new(Arc::new({
+            let handled = handled.clone();
+            move |item: MessageResult| {
+                let (handled, done) = (handled.clone(), done.clone());
+                async move {
+                    match item {
+                        Some(Err(ClientError::ConnectionClosedByServer { .. })) => {
+                            // slower than the end of the connection
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            handled.lock().unwrap().push("closed by server");
+                        }
+                        None => {
+                            handled.lock().unwrap().push("connection lost");
+                            let _ = done.lock().unwrap().take().unwrap().send(());
+                        }
+                        _ => {}
+                    }
+                    Ok(())
+                }
+            }
+        }));
+
+        queue.push(Some(Err(ClientError::ConnectionClosedByServer {
+            code: ResponseCode::Ok,
+            reason: "closed".to_owned(),
+        })));
+        queue.push(None);
+        finished.await.unwrap();
+
+        assert_eq!(
+            vec!["closed by server", "connection lost"],
+            *handled.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use rabbitmq_stream_protocol::commands::heart_beat::HeartBeatCommand;
+use tracing::{trace, warn};
+
+use crate::error::ClientError;
+
+use super::Client;
+
+/// Send heartbeats every `heartbeat` seconds and shut the connection down
+/// when nothing has been received from the server for two intervals.
+pub(crate) fn start(client: Client, heartbeat: u32) {
+    if heartbeat == 0 {
+        return;
+    }
+    let period = Duration::from_secs(heartbeat.into());
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        // the first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if client.channel.is_closed() || !client.dispatcher.is_running() {
+                break;
+            }
+            if client.dispatcher.last_received().elapsed() > period * 2 {
+                warn!(
+                    "No frames received from {}:{} for {:?}, closing the connection",
+                    client.opts.host,
+                    client.opts.port,
+                    period * 2
+                );
+                client
+                    .dispatcher
+                    .shutdown(ClientError::HeartbeatTimeout)
+                    .await;
+                break;
+            }
+            trace!("Sending heartbeat");
+            if client.send(HeartBeatCommand::default()).await.is_err() {
+                break;
+            }
+        }
+        trace!("Heartbeat task stopped");
+    });
+}
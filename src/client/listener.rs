@@ -0,0 +1,42 @@
+use std::future::Future;
+
+use rabbitmq_stream_protocol::ResponseCode;
+
+/// Events of the connections opened by a [`crate::Client`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    /// The server closed the connection to `host`:`port`, e.g. from the management UI.
+    ///
+    /// The producers and consumers of the connection first get
+    /// [`crate::error::ClientError::ConnectionClosedByServer`], then recover as from
+    /// a lost connection, following the [`crate::RecoveryPolicy`] of the environment.
+    ClosedByServer {
+        host: String,
+        port: u16,
+        code: ResponseCode,
+        reason: String,
+    },
+}
+
+#[async_trait::async_trait]
+pub trait ConnectionListener: Send + Sync {
+    async fn on_event(&self, event: ConnectionEvent);
+}
+
+#[async_trait::async_trait]
+impl<T, F> ConnectionListener for T
+where
+    F: Future<Output = ()> + Send,
+    T: Fn(ConnectionEvent) -> F + Send + Sync,
+{
+    async fn on_event(&self, event: ConnectionEvent) {
+        self(event).await
+    }
+}
+
+pub struct NopConnectionListener {}
+
+#[async_trait::async_trait]
+impl ConnectionListener for NopConnectionListener {
+    async fn on_event(&self, _event: ConnectionEvent) {}
+}
@@ -4,6 +4,7 @@ pub trait MetricsCollector: Send + Sync {
     async fn consume(&self, count: u64);
     async fn publish_confirm(&self, count: u64);
     async fn publish_error(&self, count: u64);
+    async fn crc_error(&self, _count: u64) {}
 }
 pub struct NopMetricsCollector {}
 
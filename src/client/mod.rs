@@ -2,50 +2,65 @@ mod channel;
 mod codec;
 mod dispatcher;
 mod handler;
+mod heartbeat;
+mod listener;
 mod metadata;
 mod metrics;
 mod options;
+mod publish;
+mod stream;
+mod tls;
 use crate::{error::ClientError, RabbitMQStreamResult};
 use futures::{
     stream::{SplitSink, SplitStream},
     Stream, StreamExt, TryFutureExt,
 };
+pub use listener::{ConnectionEvent, ConnectionListener};
 pub use metadata::{Broker, StreamMetadata};
 pub use metrics::MetricsCollector;
-pub use options::ClientOptions;
+pub use options::{ClientOptions, TlsConfiguration};
 use rabbitmq_stream_protocol::{
     commands::{
         close::{CloseRequest, CloseResponse},
+        consumer_update::ConsumerUpdateResponse,
         create_stream::CreateStreamCommand,
+        create_super_stream::CreateSuperStreamCommand,
         credit::CreditCommand,
         declare_publisher::DeclarePublisherCommand,
         delete::Delete,
         delete_publisher::DeletePublisherCommand,
+        delete_super_stream::DeleteSuperStreamCommand,
         generic::GenericResponse,
-        metadata::MetadataCommand,
+        metadata::{MetadataCommand, MetadataResponse},
         open::{OpenCommand, OpenResponse},
+        partitions::{PartitionsRequest, PartitionsResponse},
         peer_properties::{PeerPropertiesCommand, PeerPropertiesResponse},
         publish::PublishCommand,
         query_offset::{QueryOffsetRequest, QueryOffsetResponse},
         query_publisher_sequence::{QueryPublisherRequest, QueryPublisherResponse},
+        route::{RouteRequest, RouteResponse},
         sasl_authenticate::SaslAuthenticateCommand,
         sasl_handshake::{SaslHandshakeCommand, SaslHandshakeResponse},
         store_offset::StoreOffset,
+        stream_stats::{StreamStatsRequest, StreamStatsResponse},
         subscribe::{OffsetSpecification, SubscribeCommand},
         tune::TunesCommand,
         unsubscribe::UnSubscribeCommand,
     },
+    compression::Compression,
     message::Message,
-    types::PublishedMessage,
+    types::{PublishedMessage, PublishedSubEntry},
     FromResponse, Request, Response, ResponseCode, ResponseKind,
 };
-use tracing::trace;
+use tracing::{trace, warn};
 
 pub use self::handler::{MessageHandler, MessageResult};
 use self::{
     channel::{channel, ChannelReceiver, ChannelSender},
     codec::RabbitMqStreamCodec,
     dispatcher::Dispatcher,
+    handler::HandlerQueue,
+    stream::GenericTcpStream,
 };
 
 use std::future::Future;
@@ -57,44 +72,85 @@ use tokio::sync::RwLock;
 use tokio::{net::TcpStream, sync::Notify};
 use tokio_util::codec::Framed;
 
-type SinkConnection = SplitSink<Framed<TcpStream, RabbitMqStreamCodec>, Request>;
-type StreamConnection = SplitStream<Framed<TcpStream, RabbitMqStreamCodec>>;
+type SinkConnection = SplitSink<Framed<GenericTcpStream, RabbitMqStreamCodec>, Request>;
+type StreamConnection = SplitStream<Framed<GenericTcpStream, RabbitMqStreamCodec>>;
 
 pub struct ClientState {
     server_properties: HashMap<String, String>,
     connection_properties: HashMap<String, String>,
-    handler: Option<Arc<dyn MessageHandler>>,
+    handler: Option<HandlerQueue>,
+    publisher_handlers: HashMap<u8, HandlerQueue>,
+    subscription_handlers: HashMap<u8, HandlerQueue>,
     heartbeat: u32,
     max_frame_size: u32,
 }
 
+impl ClientState {
+    /// Handler of the publisher or subscription the response is for, or the default one
+    fn handler_for(&self, kind: &ResponseKind) -> Option<&HandlerQueue> {
+        let handler = match kind {
+            ResponseKind::Deliver(deliver) => {
+                self.subscription_handlers.get(&deliver.subscription_id)
+            }
+            ResponseKind::ConsumerUpdate(update) => {
+                self.subscription_handlers.get(&update.subscription_id())
+            }
+            ResponseKind::PublishConfirm(confirm) => {
+                self.publisher_handlers.get(&confirm.publisher_id)
+            }
+            ResponseKind::PublishError(error) => self.publisher_handlers.get(&error.publisher_id),
+            _ => None,
+        };
+        handler.or(self.handler.as_ref())
+    }
+
+    /// Handlers of all the publishers and subscriptions, then the default one
+    fn all_handlers(&self) -> impl Iterator<Item = &HandlerQueue> {
+        self.publisher_handlers
+            .values()
+            .chain(self.subscription_handlers.values())
+            .chain(self.handler.iter())
+    }
+}
+
 #[async_trait::async_trait]
 impl MessageHandler for Client {
     async fn handle_message(&self, item: MessageResult) -> RabbitMQStreamResult<()> {
         match &item {
             Some(Ok(response)) => match response.kind_ref() {
                 ResponseKind::Tunes(tune) => self.handle_tune_command(tune).await,
-                _ => {
-                    if let Some(handler) = self.state.read().await.handler.as_ref() {
-                        let handler = handler.clone();
-
-                        tokio::task::spawn(async move { handler.handle_message(item).await });
+                ResponseKind::Heartbeat(_) => trace!("Heartbeat received"),
+                ResponseKind::CloseRequest(close) => self.handle_close_request(close).await,
+                ResponseKind::MetadataUpdate(update) => {
+                    trace!("Metadata update for stream {}", update.stream());
+                    let state = self.state.read().await;
+                    // each publisher or subscription checks if the update is about its stream
+                    for handler in state.all_handlers() {
+                        handler.push(Some(Ok(Response::new(
+                            response.header().clone(),
+                            ResponseKind::MetadataUpdate(update.clone()),
+                        ))));
+                    }
+                }
+                kind => {
+                    if let Some(handler) = self.state.read().await.handler_for(kind) {
+                        // queued so that each subscription handles its chunks in order
+                        handler.push(item);
                     }
                 }
             },
             Some(Err(err)) => {
                 trace!(?err);
-                if let Some(handler) = self.state.read().await.handler.as_ref() {
-                    let handler = handler.clone();
-
-                    tokio::task::spawn(async move { handler.handle_message(item).await });
+                for handler in self.state.read().await.all_handlers() {
+                    handler.push(Some(Err(err.duplicate())));
                 }
             }
             None => {
                 trace!("Closing client");
-                if let Some(handler) = self.state.read().await.handler.as_ref() {
-                    let handler = handler.clone();
-                    tokio::task::spawn(async move { handler.handle_message(None).await });
+                // the dispatcher stopped reading, release the writing side of the connection
+                self.channel.shutdown().await;
+                for handler in self.state.read().await.all_handlers() {
+                    handler.push(None);
                 }
             }
         }
@@ -127,6 +183,8 @@ impl Client {
             server_properties: HashMap::new(),
             connection_properties: HashMap::new(),
             handler: None,
+            publisher_handlers: HashMap::new(),
+            subscription_handlers: HashMap::new(),
             heartbeat: broker.heartbeat,
             max_frame_size: broker.max_frame_size,
         };
@@ -157,7 +215,60 @@ impl Client {
     pub async fn set_handler<H: MessageHandler>(&self, handler: H) {
         let mut state = self.state.write().await;
 
-        state.handler = Some(Arc::new(handler));
+        state.handler = Some(HandlerQueue::new(Arc::new(handler)));
+    }
+
+    /// Handle the confirms and errors of `publisher_id` with `handler` instead of the default one
+    pub async fn set_publisher_handler<H: MessageHandler>(&self, publisher_id: u8, handler: H) {
+        let mut state = self.state.write().await;
+
+        state
+            .publisher_handlers
+            .insert(publisher_id, HandlerQueue::new(Arc::new(handler)));
+    }
+
+    pub async fn remove_publisher_handler(&self, publisher_id: u8) {
+        self.state
+            .write()
+            .await
+            .publisher_handlers
+            .remove(&publisher_id);
+    }
+
+    /// Handle the deliveries and updates of `subscription_id` with `handler` instead of the default one
+    pub async fn set_subscription_handler<H: MessageHandler>(
+        &self,
+        subscription_id: u8,
+        handler: H,
+    ) {
+        let mut state = self.state.write().await;
+
+        state
+            .subscription_handlers
+            .insert(subscription_id, HandlerQueue::new(Arc::new(handler)));
+    }
+
+    pub async fn remove_subscription_handler(&self, subscription_id: u8) {
+        self.state
+            .write()
+            .await
+            .subscription_handlers
+            .remove(&subscription_id);
+    }
+
+    /// Check if the connection is closed or lost
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed() || !self.dispatcher.is_running()
+    }
+
+    /// Options the client connected with
+    pub(crate) fn options(&self) -> &ClientOptions {
+        &self.opts
+    }
+
+    /// Check if `other` is a clone of this client, sharing the same connection
+    pub(crate) fn same_connection(&self, other: &Client) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
     }
 
     pub async fn close(&self) -> RabbitMQStreamResult<()> {
@@ -215,6 +326,75 @@ impl Client {
             .await
     }
 
+    pub async fn create_super_stream(
+        &self,
+        super_stream: &str,
+        partitions: Vec<String>,
+        binding_keys: Vec<String>,
+        options: HashMap<String, String>,
+    ) -> RabbitMQStreamResult<GenericResponse> {
+        self.send_and_receive(|correlation_id| {
+            CreateSuperStreamCommand::new(
+                correlation_id,
+                super_stream.to_owned(),
+                partitions,
+                binding_keys,
+                options,
+            )
+        })
+        .await
+    }
+
+    pub async fn delete_super_stream(
+        &self,
+        super_stream: &str,
+    ) -> RabbitMQStreamResult<GenericResponse> {
+        self.send_and_receive(|correlation_id| {
+            DeleteSuperStreamCommand::new(correlation_id, super_stream.to_owned())
+        })
+        .await
+    }
+
+    /// Streams of `super_stream` bound to `routing_key`
+    pub async fn route(
+        &self,
+        routing_key: &str,
+        super_stream: &str,
+    ) -> RabbitMQStreamResult<Vec<String>> {
+        self.send_and_receive::<RouteResponse, _, _>(|correlation_id| {
+            RouteRequest::new(
+                correlation_id,
+                routing_key.to_owned(),
+                super_stream.to_owned(),
+            )
+        })
+        .await
+        .map(|route| route.from_response())
+    }
+
+    /// Partitions of `super_stream`, in creation order
+    pub async fn partitions(&self, super_stream: &str) -> RabbitMQStreamResult<Vec<String>> {
+        self.send_and_receive::<PartitionsResponse, _, _>(|correlation_id| {
+            PartitionsRequest::new(correlation_id, super_stream.to_owned())
+        })
+        .await
+        .map(|partitions| partitions.from_response())
+    }
+
+    /// Reply to a consumer update of the server with the offset to consume from
+    pub async fn consumer_update(
+        &self,
+        correlation_id: u32,
+        offset_specification: OffsetSpecification,
+    ) -> RabbitMQStreamResult<()> {
+        self.send(ConsumerUpdateResponse::new(
+            correlation_id,
+            ResponseCode::Ok,
+            offset_specification,
+        ))
+        .await
+    }
+
     pub async fn credit(&self, subscription_id: u8, credit: u16) -> RabbitMQStreamResult<()> {
         self.send(CreditCommand::new(subscription_id, credit)).await
     }
@@ -228,6 +408,19 @@ impl Client {
             .map(metadata::from_response)
     }
 
+    /// Check if `stream` exists, even while it has no leader available
+    pub async fn stream_exists(&self, stream: &str) -> RabbitMQStreamResult<bool> {
+        let response = self
+            .send_and_receive::<MetadataResponse, _, _>(|correlation_id| {
+                MetadataCommand::new(correlation_id, vec![stream.to_owned()])
+            })
+            .await?;
+
+        Ok(response.stream_metadata.iter().any(|metadata| {
+            metadata.stream_name == stream && metadata.code != ResponseCode::StreamDoesNotExist
+        }))
+    }
+
     pub async fn store_offset(
         &self,
         reference: &str,
@@ -242,12 +435,34 @@ impl Client {
         .await
     }
 
+    /// Query the offset stored for `reference`, fails with [`ResponseCode::NoOffset`] if none was stored
     pub async fn query_offset(&self, reference: String, stream: &str) -> Result<u64, ClientError> {
-        self.send_and_receive::<QueryOffsetResponse, _, _>(|correlation_id| {
-            QueryOffsetRequest::new(correlation_id, reference, stream.to_owned())
-        })
-        .await
-        .map(|query_offset| query_offset.from_response())
+        let response = self
+            .send_and_receive::<QueryOffsetResponse, _, _>(|correlation_id| {
+                QueryOffsetRequest::new(correlation_id, reference, stream.to_owned())
+            })
+            .await?;
+
+        if response.is_ok() {
+            Ok(response.from_response())
+        } else {
+            Err(ClientError::RequestError(response.code().clone()))
+        }
+    }
+
+    /// Statistics of `stream`, e.g. `first_chunk_id` and `committed_chunk_id`
+    pub async fn stream_stats(&self, stream: &str) -> Result<HashMap<String, i64>, ClientError> {
+        let response = self
+            .send_and_receive::<StreamStatsResponse, _, _>(|correlation_id| {
+                StreamStatsRequest::new(correlation_id, stream.to_owned())
+            })
+            .await?;
+
+        if response.is_ok() {
+            Ok(response.from_response())
+        } else {
+            Err(ClientError::RequestError(response.code().clone()))
+        }
     }
 
     pub async fn declare_publisher(
@@ -286,7 +501,6 @@ impl Client {
         let mut messages_to_publish = Vec::with_capacity(messages.len());
         let mut sequences = Vec::with_capacity(messages.len());
         let len = messages.len();
-        // TODO batch publish with max frame size check
         for message in messages {
             let publishing_id = match message.publishing_id() {
                 Some(publishing_id) => *publishing_id,
@@ -297,8 +511,55 @@ impl Client {
             sequences.push(publishing_id);
             messages_to_publish.push(PublishedMessage::new(publishing_id, message));
         }
-        self.send(PublishCommand::new(publisher_id, messages_to_publish))
-            .await?;
+
+        let max_frame_size = self.state.read().await.max_frame_size;
+        for frame in publish::split_in_frames(messages_to_publish, max_frame_size)? {
+            self.send(PublishCommand::new(publisher_id, frame)).await?;
+        }
+
+        self.opts.collector.publish(len as u64).await;
+
+        Ok(sequences)
+    }
+
+    /// Publish each of `entries` as a single sub-entry compressed with `compression`.
+    ///
+    /// A sub-entry is published with the id of its last message, the returned sequences
+    /// are the ids of the sub-entries.
+    pub async fn publish_sub_entries(
+        &self,
+        publisher_id: u8,
+        entries: Vec<Vec<Message>>,
+        compression: Compression,
+    ) -> RabbitMQStreamResult<Vec<u64>> {
+        let mut sub_entries = Vec::with_capacity(entries.len());
+        let mut sequences = Vec::with_capacity(entries.len());
+        let mut len = 0;
+        for mut messages in entries {
+            let mut publishing_id = 0;
+            for message in &mut messages {
+                publishing_id = match message.publishing_id() {
+                    Some(publishing_id) => *publishing_id,
+                    None => self
+                        .publish_sequence
+                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+                };
+                message.set_publishing_id(publishing_id);
+            }
+            len += messages.len();
+            sequences.push(publishing_id);
+            sub_entries.push(PublishedSubEntry::new(
+                publishing_id,
+                compression,
+                &messages,
+            )?);
+        }
+
+        let max_frame_size = self.state.read().await.max_frame_size;
+        for frame in publish::split_in_frames(sub_entries, max_frame_size)? {
+            self.send(PublishCommand::sub_entries(publisher_id, frame))
+                .await?;
+        }
 
         self.opts.collector.publish(len as u64).await;
 
@@ -327,6 +588,13 @@ impl Client {
         ClientError,
     > {
         let stream = TcpStream::connect((broker.host.as_str(), broker.port)).await?;
+        let stream = if broker.tls.enabled {
+            GenericTcpStream::SecureTcp(Box::new(
+                tls::connect(&broker.tls, &broker.host, stream).await?,
+            ))
+        } else {
+            GenericTcpStream::Tcp(stream)
+        };
         let stream = Framed::new(stream, RabbitMqStreamCodec {});
 
         let (sink, stream) = stream.split();
@@ -350,6 +618,8 @@ impl Client {
 
         self.wait_for_tune_data().await?;
 
+        heartbeat::start(self.clone(), self.state.read().await.heartbeat);
+
         self.with_state_lock(self.open(), |state, connection_properties| {
             state.connection_properties = connection_properties;
         })
@@ -372,13 +642,6 @@ impl Client {
         Ok(())
     }
 
-    fn max_value(&self, client: u32, server: u32) -> u32 {
-        match (client, server) {
-            (client, server) if client == 0 || server == 0 => client.max(server),
-            (client, server) => client.max(server),
-        }
-    }
-
     async fn wait_for_tune_data(&mut self) -> Result<(), ClientError> {
         self.tune_notifier.notified().await;
         Ok(())
@@ -419,12 +682,19 @@ impl Client {
         M: FnOnce(u32) -> R,
     {
         let (correlation_id, mut receiver) = self.dispatcher.response_channel().await;
+        // checked once registered, the dispatcher drops the requests waiting when it stops
+        if !self.dispatcher.is_running() {
+            return Err(ClientError::ConnectionClosed);
+        }
 
         self.channel
             .send(msg_factory(correlation_id).into())
             .await?;
 
-        let response = receiver.recv().await.expect("It should contain a response");
+        let response = tokio::time::timeout(self.opts.request_timeout, receiver.recv())
+            .await
+            .map_err(|_| ClientError::Timeout)?
+            .ok_or(ClientError::ConnectionClosed)?;
 
         self.handle_response::<T>(response).await
     }
@@ -462,10 +732,45 @@ impl Client {
         .map(|peer_properties| peer_properties.server_properties)
     }
 
+    /// Reply to the server closing the connection and tell the publishers,
+    /// the subscriptions and the listener why.
+    ///
+    /// The reason is queued to the handlers before the end of the connection,
+    /// which they get once the server drops the socket.
+    async fn handle_close_request(&self, close: &CloseRequest) {
+        warn!(
+            "Connection closed by the server with status {:?}: {}",
+            close.closing_code(),
+            close.closing_reason()
+        );
+        let _ = self
+            .channel
+            .send(CloseResponse::new(close.correlation_id(), ResponseCode::Ok).into())
+            .await;
+        self.channel.set_closed();
+
+        for handler in self.state.read().await.all_handlers() {
+            handler.push(Some(Err(ClientError::ConnectionClosedByServer {
+                code: close.closing_code().clone(),
+                reason: close.closing_reason().to_owned(),
+            })));
+        }
+
+        self.opts
+            .connection_listener
+            .on_event(ConnectionEvent::ClosedByServer {
+                host: self.opts.host.clone(),
+                port: self.opts.port,
+                code: close.closing_code().clone(),
+                reason: close.closing_reason().to_owned(),
+            })
+            .await;
+    }
+
     async fn handle_tune_command(&self, tunes: &TunesCommand) {
         let mut state = self.state.write().await;
-        state.heartbeat = self.max_value(self.opts.heartbeat, tunes.heartbeat);
-        state.max_frame_size = self.max_value(self.opts.max_frame_size, tunes.max_frame_size);
+        state.heartbeat = negotiate(self.opts.heartbeat, tunes.heartbeat);
+        state.max_frame_size = negotiate(self.opts.max_frame_size, tunes.max_frame_size);
 
         let heart_beat = state.heartbeat;
         let max_frame_size = state.max_frame_size;
@@ -479,3 +784,61 @@ impl Client {
         self.tune_notifier.notify_one();
     }
 }
+
+#[cfg(test)]
+impl Client {
+    /// Client connected to `port` of the local host without the handshake of the protocol,
+    /// publishing in frames of up to `max_frame_size` bytes. Its requests time out after 100ms.
+    pub(crate) async fn unopened(port: u16, max_frame_size: u32) -> Client {
+        let opts = ClientOptions {
+            host: "127.0.0.1".to_owned(),
+            port,
+            request_timeout: std::time::Duration::from_millis(100),
+            ..Default::default()
+        };
+        let (sender, receiver) = Client::create_connection(&opts).await.unwrap();
+
+        let state = ClientState {
+            server_properties: HashMap::new(),
+            connection_properties: HashMap::new(),
+            handler: None,
+            publisher_handlers: HashMap::new(),
+            subscription_handlers: HashMap::new(),
+            heartbeat: 0,
+            max_frame_size,
+        };
+        let client = Client {
+            dispatcher: Dispatcher::new(),
+            opts,
+            channel: Arc::new(sender),
+            state: Arc::new(RwLock::new(state)),
+            tune_notifier: Arc::new(Notify::new()),
+            publish_sequence: Arc::new(AtomicU64::new(1)),
+        };
+        client.dispatcher.set_handler(client.clone()).await;
+        client.dispatcher.start(receiver).await;
+        client
+    }
+}
+
+/// Value agreed on for a tune setting, the lowest one unless a side has no limit (0)
+fn negotiate(client: u32, server: u32) -> u32 {
+    match (client, server) {
+        (client, server) if client == 0 || server == 0 => client.max(server),
+        (client, server) => client.min(server),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::negotiate;
+
+    #[test]
+    fn negotiate_tune_test() {
+        assert_eq!(1_048_576, negotiate(1_048_576, 2_097_152));
+        assert_eq!(131_072, negotiate(1_048_576, 131_072));
+        assert_eq!(60, negotiate(0, 60));
+        assert_eq!(60, negotiate(60, 0));
+        assert_eq!(0, negotiate(0, 0));
+    }
+}
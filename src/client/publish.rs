@@ -0,0 +1,104 @@
+use std::mem;
+
+use rabbitmq_stream_protocol::codec::Encoder;
+
+use crate::error::ClientError;
+
+/// Bytes of a publish frame besides its entries: size, key, version, publisher id and entry count
+const PUBLISH_FRAME_OVERHEAD: u32 = 4 + 2 + 2 + 1 + 4;
+
+/// Split `entries` in groups which fit each in a publish frame of `max_frame_size` bytes,
+/// a `max_frame_size` of 0 means no limit.
+///
+/// Fails without splitting anything if a single entry can never fit.
+pub(crate) fn split_in_frames<T: Encoder>(
+    entries: Vec<T>,
+    max_frame_size: u32,
+) -> Result<Vec<Vec<T>>, ClientError> {
+    if max_frame_size == 0 {
+        return Ok(vec![entries]);
+    }
+
+    let max_entries_size = max_frame_size.saturating_sub(PUBLISH_FRAME_OVERHEAD);
+    if let Some(size) = entries
+        .iter()
+        .map(Encoder::encoded_size)
+        .find(|size| *size > max_entries_size)
+    {
+        return Err(ClientError::MessageTooLarge {
+            size,
+            max_frame_size,
+        });
+    }
+
+    let mut frames = Vec::new();
+    let mut frame = Vec::new();
+    let mut frame_size = 0;
+    for entry in entries {
+        let size = entry.encoded_size();
+        if frame_size + size > max_entries_size && !frame.is_empty() {
+            frames.push(mem::take(&mut frame));
+            frame_size = 0;
+        }
+        frame_size += size;
+        frame.push(entry);
+    }
+    if !frame.is_empty() {
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use rabbitmq_stream_protocol::{codec::Encoder, message::Message, types::PublishedMessage};
+
+    use super::{split_in_frames, PUBLISH_FRAME_OVERHEAD};
+    use crate::error::ClientError;
+
+    fn messages(count: u64, size: usize) -> Vec<PublishedMessage> {
+        (0..count)
+            .map(|id| PublishedMessage::new(id, Message::builder().body(vec![0; size]).build()))
+            .collect()
+    }
+
+    #[test]
+    fn split_in_frames_test() {
+        let entry_size = messages(1, 100)[0].encoded_size();
+        let max_frame_size = PUBLISH_FRAME_OVERHEAD + 3 * entry_size;
+
+        let frames = split_in_frames(messages(7, 100), max_frame_size).unwrap();
+        assert_eq!(
+            vec![3, 3, 1],
+            frames.iter().map(Vec::len).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            (0..7).collect::<Vec<_>>(),
+            frames
+                .iter()
+                .flatten()
+                .map(|message| message.publishing_id)
+                .collect::<Vec<_>>()
+        );
+
+        let frames = split_in_frames(messages(7, 100), 0).unwrap();
+        assert_eq!(vec![7], frames.iter().map(Vec::len).collect::<Vec<_>>());
+
+        assert!(split_in_frames(messages(0, 100), max_frame_size)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn split_in_frames_message_too_large_test() {
+        let result = split_in_frames(messages(2, 1000), 500);
+
+        assert!(matches!(
+            result,
+            Err(ClientError::MessageTooLarge {
+                max_frame_size: 500,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::client::TlsStream;
+
+/// Transport used by the client, either plain TCP or TLS over TCP
+pub enum GenericTcpStream {
+    Tcp(TcpStream),
+    SecureTcp(Box<TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for GenericTcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GenericTcpStream::Tcp(tcp) => Pin::new(tcp).poll_read(cx, buf),
+            GenericTcpStream::SecureTcp(tls) => Pin::new(tls).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for GenericTcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            GenericTcpStream::Tcp(tcp) => Pin::new(tcp).poll_write(cx, buf),
+            GenericTcpStream::SecureTcp(tls) => Pin::new(tls).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GenericTcpStream::Tcp(tcp) => Pin::new(tcp).poll_flush(cx),
+            GenericTcpStream::SecureTcp(tls) => Pin::new(tls).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GenericTcpStream::Tcp(tcp) => Pin::new(tcp).poll_shutdown(cx),
+            GenericTcpStream::SecureTcp(tls) => Pin::new(tls).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::{convert::TryFrom, fs::File, io::BufReader, sync::Arc, time::SystemTime};
+
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName,
+};
+use tokio::net::TcpStream;
+use tokio_rustls::{client::TlsStream, TlsConnector};
+
+use crate::error::ClientError;
+
+use super::options::TlsConfiguration;
+
+pub async fn connect(
+    configuration: &TlsConfiguration,
+    host: &str,
+    stream: TcpStream,
+) -> Result<TlsStream<TcpStream>, ClientError> {
+    let config = client_config(configuration)?;
+    let domain = ServerName::try_from(host)
+        .map_err(|_| rustls::Error::General(format!("Invalid dns name {}", host)))?;
+
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(domain, stream)
+        .await?;
+
+    Ok(stream)
+}
+
+fn client_config(configuration: &TlsConfiguration) -> Result<ClientConfig, ClientError> {
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store(configuration)?);
+
+    let mut config = match (
+        &configuration.client_certificates_path,
+        &configuration.client_keys_path,
+    ) {
+        (Some(certificates_path), Some(keys_path)) => {
+            builder.with_single_cert(load_certificates(certificates_path)?, load_key(keys_path)?)?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+
+    if configuration.trust_everything {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(TrustEverything {}));
+    }
+
+    Ok(config)
+}
+
+fn root_store(configuration: &TlsConfiguration) -> Result<RootCertStore, ClientError> {
+    let mut roots = RootCertStore::empty();
+
+    match &configuration.root_certificates_path {
+        Some(path) => {
+            for certificate in load_certificates(path)? {
+                roots
+                    .add(&certificate)
+                    .map_err(|err| rustls::Error::General(err.to_string()))?;
+            }
+        }
+        None => {
+            roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|anchor| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    anchor.subject,
+                    anchor.spki,
+                    anchor.name_constraints,
+                )
+            }))
+        }
+    }
+    Ok(roots)
+}
+
+fn load_certificates(path: &str) -> Result<Vec<Certificate>, ClientError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certificates = rustls_pemfile::certs(&mut reader)?;
+
+    Ok(certificates.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &str) -> Result<PrivateKey, ClientError> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => {}
+        }
+    }
+    Err(rustls::Error::General(format!("No private key found in {}", path)).into())
+}
+
+/// Server certificate verifier which accepts everything
+struct TrustEverything {}
+
+impl ServerCertVerifier for TrustEverything {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rustls::ServerConfig;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::TlsAcceptor;
+
+    use super::{client_config, connect, load_certificates, load_key, root_store};
+    use crate::{client::options::TlsConfiguration, error::ClientError};
+
+    fn resource(name: &str) -> String {
+        format!(
+            "{}/tests/resources/tls/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    /// Connects to a local TLS server presenting a `localhost` certificate signed by the test CA
+    async fn handshake(configuration: TlsConfiguration) -> Result<(), ClientError> {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                load_certificates(&resource("server_certificate.pem")).unwrap(),
+                load_key(&resource("server_key.pem")).unwrap(),
+            )
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = TlsAcceptor::from(Arc::new(config)).accept(stream).await;
+        });
+
+        let stream = TcpStream::connect(address).await.unwrap();
+        connect(&configuration, "localhost", stream)
+            .await
+            .map(|_| ())
+    }
+
+    #[test]
+    fn root_certificates_test() {
+        let roots = root_store(&TlsConfiguration::enabled()).unwrap();
+        assert!(!roots.is_empty());
+
+        let roots = root_store(
+            &TlsConfiguration::enabled().root_certificates(&resource("ca_certificate.pem")),
+        )
+        .unwrap();
+        assert_eq!(1, roots.len());
+    }
+
+    #[test]
+    fn client_certificate_test() {
+        let configuration = TlsConfiguration::enabled()
+            .root_certificates(&resource("ca_certificate.pem"))
+            .client_certificate(
+                &resource("client_certificate.pem"),
+                &resource("client_key.pem"),
+            );
+        assert!(client_config(&configuration).is_ok());
+
+        let configuration = TlsConfiguration::enabled().client_certificate(
+            &resource("client_certificate.pem"),
+            &resource("client_certificate.pem"),
+        );
+        assert!(matches!(
+            client_config(&configuration),
+            Err(ClientError::Tls(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_pem_test() {
+        let configuration = TlsConfiguration::enabled().root_certificates(&resource("invalid.pem"));
+        assert!(matches!(
+            client_config(&configuration),
+            Err(ClientError::Tls(_))
+        ));
+
+        let configuration = TlsConfiguration::enabled().root_certificates(&resource("missing.pem"));
+        assert!(matches!(
+            client_config(&configuration),
+            Err(ClientError::Io(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn handshake_test() {
+        assert!(handshake(
+            TlsConfiguration::enabled().root_certificates(&resource("ca_certificate.pem"))
+        )
+        .await
+        .is_ok());
+
+        assert!(handshake(TlsConfiguration::enabled()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn trust_everything_test() {
+        assert!(
+            handshake(TlsConfiguration::enabled().trust_everything(true))
+                .await
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+};
+
+use tokio::sync::{watch, Mutex};
+use tracing::trace;
+
+use crate::{
+    client::{Broker, Client},
+    RabbitMQStreamResult,
+};
+
+/// What the pool needs of a connection, implemented by [`Client`]
+#[async_trait::async_trait]
+pub(crate) trait PoolConnection: Clone + Send + Sync {
+    fn is_closed(&self) -> bool;
+
+    fn same_connection(&self, other: &Self) -> bool;
+
+    async fn close(&self) -> RabbitMQStreamResult<()>;
+}
+
+#[async_trait::async_trait]
+impl PoolConnection for Client {
+    fn is_closed(&self) -> bool {
+        Client::is_closed(self)
+    }
+
+    fn same_connection(&self, other: &Self) -> bool {
+        Client::same_connection(self, other)
+    }
+
+    async fn close(&self) -> RabbitMQStreamResult<()> {
+        Client::close(self).await
+    }
+}
+
+/// Connections shared by the producers or by the consumers of an [`crate::Environment`].
+///
+/// Each connection carries up to `max_ids` publishers or subscriptions, identified by ids
+/// unique on the connection, and is closed when the last of them is released.
+pub(crate) struct ConnectionPool<C = Client> {
+    max_ids: usize,
+    connections: Mutex<HashMap<String, Vec<PooledConnection<C>>>>,
+    reservations: AtomicU64,
+}
+
+struct PooledConnection<C> {
+    /// `None` while the connection is being opened
+    client: Option<C>,
+    ids: BTreeSet<u8>,
+    reservation: u64,
+    /// Changes once the connection is opened or failed to open
+    opened: watch::Receiver<()>,
+}
+
+impl<C: PoolConnection> PooledConnection<C> {
+    fn free_id(&self) -> Option<u8> {
+        (0..=u8::MAX).find(|id| !self.ids.contains(id))
+    }
+
+    /// Closed, or abandoned while being opened
+    fn is_closed(&self) -> bool {
+        match &self.client {
+            Some(client) => client.is_closed(),
+            None => self.opened.has_changed().is_err(),
+        }
+    }
+}
+
+impl<C: PoolConnection> ConnectionPool<C> {
+    pub(crate) fn new(max_ids: usize) -> ConnectionPool<C> {
+        ConnectionPool {
+            max_ids: max_ids.clamp(1, u8::MAX as usize + 1),
+            connections: Mutex::new(HashMap::new()),
+            reservations: AtomicU64::new(0),
+        }
+    }
+
+    /// Get a connection to `broker` with a free id, opened with `connect` if needed.
+    ///
+    /// The pool is not locked while `connect` runs, the new connection is reserved
+    /// and the acquisitions which can share it wait for it to be opened.
+    pub(crate) async fn acquire<Fut>(
+        &self,
+        broker: &Broker,
+        connect: impl FnOnce() -> Fut,
+    ) -> RabbitMQStreamResult<(C, u8)>
+    where
+        Fut: Future<Output = RabbitMQStreamResult<C>>,
+    {
+        let key = format!("{}:{}", broker.host, broker.port);
+        // the waiters are woken up once the notifier is dropped
+        let (reservation, _notifier) = loop {
+            let mut connections = self.connections.lock().await;
+            let pooled = connections.entry(key.clone()).or_default();
+            pooled.retain(|connection| !connection.is_closed());
+
+            let mut opening = None;
+            for connection in pooled.iter_mut() {
+                if connection.ids.len() >= self.max_ids {
+                    continue;
+                }
+                match (&connection.client, connection.free_id()) {
+                    (Some(client), Some(id)) => {
+                        connection.ids.insert(id);
+                        return Ok((client.clone(), id));
+                    }
+                    (None, Some(_)) => opening = Some(connection.opened.clone()),
+                    _ => {}
+                }
+            }
+
+            match opening {
+                Some(mut opened) => {
+                    drop(connections);
+                    // the connection may fail to open, look again either way
+                    let _ = opened.changed().await;
+                }
+                None => {
+                    let reservation = self.reservations.fetch_add(1, Relaxed);
+                    let (notifier, opened) = watch::channel(());
+                    pooled.push(PooledConnection {
+                        client: None,
+                        ids: BTreeSet::from([0]),
+                        reservation,
+                        opened,
+                    });
+                    break (reservation, notifier);
+                }
+            }
+        };
+
+        trace!(
+            "Opening pooled connection to {}:{}",
+            broker.host,
+            broker.port
+        );
+        let result = connect().await;
+
+        let mut connections = self.connections.lock().await;
+        if let Some(pooled) = connections.get_mut(&key) {
+            if let Some(index) = pooled
+                .iter()
+                .position(|connection| connection.reservation == reservation)
+            {
+                match &result {
+                    Ok(client) => pooled[index].client = Some(client.clone()),
+                    Err(_) => {
+                        pooled.remove(index);
+                    }
+                }
+            }
+        }
+        connections.retain(|_, pooled| !pooled.is_empty());
+
+        result.map(|client| (client, 0))
+    }
+
+    /// Give back `id` of `client`, closing the connection if nothing else uses it
+    pub(crate) async fn release(&self, client: &C, id: u8) {
+        let mut connections = self.connections.lock().await;
+
+        let mut unused = None;
+        for pooled in connections.values_mut() {
+            if let Some(index) = pooled.iter().position(|connection| {
+                matches!(&connection.client, Some(pooled) if pooled.same_connection(client))
+            }) {
+                pooled[index].ids.remove(&id);
+                if pooled[index].ids.is_empty() {
+                    unused = pooled.remove(index).client;
+                }
+                break;
+            }
+        }
+        connections.retain(|_, pooled| !pooled.is_empty());
+        drop(connections);
+
+        if let Some(client) = unused {
+            if !client.is_closed() {
+                trace!("Closing unused pooled connection");
+                let _ = client.close().await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::{ConnectionPool, PoolConnection};
+    use crate::{client::Broker, error::ClientError, RabbitMQStreamResult};
+
+    #[derive(Clone)]
+    struct FakeConnection {
+        id: usize,
+        closed: Arc<AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl PoolConnection for FakeConnection {
+        fn is_closed(&self) -> bool {
+            self.closed.load(SeqCst)
+        }
+
+        fn same_connection(&self, other: &Self) -> bool {
+            self.id == other.id
+        }
+
+        async fn close(&self) -> RabbitMQStreamResult<()> {
+            self.closed.store(true, SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Opens fake connections, counting them
+    #[derive(Clone, Default)]
+    struct Connector(Arc<AtomicUsize>);
+
+    impl Connector {
+        async fn connect(&self) -> RabbitMQStreamResult<FakeConnection> {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(FakeConnection {
+                id: self.0.fetch_add(1, SeqCst),
+                closed: Arc::new(AtomicBool::new(false)),
+            })
+        }
+
+        fn opened(&self) -> usize {
+            self.0.load(SeqCst)
+        }
+    }
+
+    fn broker() -> Broker {
+        Broker {
+            host: "localhost".to_owned(),
+            port: 5552,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_allocate_ids_on_one_connection() {
+        let pool = ConnectionPool::new(256);
+        let connector = Connector::default();
+
+        for expected in 0..3 {
+            let (connection, id) = pool
+                .acquire(&broker(), || connector.connect())
+                .await
+                .unwrap();
+            assert_eq!(0, connection.id);
+            assert_eq!(expected, id);
+        }
+        assert_eq!(1, connector.opened());
+    }
+
+    #[tokio::test]
+    async fn should_reuse_released_ids_and_close_unused_connections() {
+        let pool = ConnectionPool::new(256);
+        let connector = Connector::default();
+
+        let (connection, _) = pool
+            .acquire(&broker(), || connector.connect())
+            .await
+            .unwrap();
+        pool.acquire(&broker(), || connector.connect())
+            .await
+            .unwrap();
+
+        pool.release(&connection, 0).await;
+        let (_, id) = pool
+            .acquire(&broker(), || connector.connect())
+            .await
+            .unwrap();
+        assert_eq!(0, id);
+
+        pool.release(&connection, 0).await;
+        assert!(!connection.is_closed());
+        pool.release(&connection, 1).await;
+        assert!(connection.is_closed());
+
+        let (connection, id) = pool
+            .acquire(&broker(), || connector.connect())
+            .await
+            .unwrap();
+        assert_eq!(1, connection.id);
+        assert_eq!(0, id);
+    }
+
+    #[tokio::test]
+    async fn should_open_a_new_connection_past_the_id_limit() {
+        let pool = ConnectionPool::new(1000);
+        let connector = Connector::default();
+
+        for expected in 0..=u8::MAX {
+            let (connection, id) = pool
+                .acquire(&broker(), || connector.connect())
+                .await
+                .unwrap();
+            assert_eq!(0, connection.id);
+            assert_eq!(expected, id);
+        }
+
+        let (connection, id) = pool
+            .acquire(&broker(), || connector.connect())
+            .await
+            .unwrap();
+        assert_eq!(1, connection.id);
+        assert_eq!(0, id);
+
+        let pool = ConnectionPool::new(2);
+        for _ in 0..3 {
+            pool.acquire(&broker(), || connector.connect())
+                .await
+                .unwrap();
+        }
+        assert_eq!(4, connector.opened());
+    }
+
+    #[tokio::test]
+    async fn should_share_a_connection_being_opened() {
+        let pool = Arc::new(ConnectionPool::new(256));
+        let connector = Connector::default();
+
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let (pool, connector) = (pool.clone(), connector.clone());
+                tokio::spawn(async move {
+                    pool.acquire(&broker(), || connector.connect())
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+
+        let mut ids = Vec::new();
+        for task in tasks {
+            ids.push(task.await.unwrap().1);
+        }
+        ids.sort_unstable();
+        assert_eq!(vec![0, 1, 2, 3, 4], ids);
+        assert_eq!(1, connector.opened());
+    }
+
+    #[tokio::test]
+    async fn should_forget_a_connection_which_failed_to_open() {
+        let pool = ConnectionPool::new(256);
+        let connector = Connector::default();
+
+        let result = pool
+            .acquire(&broker(), || async {
+                Err::<FakeConnection, _>(ClientError::ConnectionClosed)
+            })
+            .await;
+        assert!(result.is_err());
+
+        let (_, id) = pool
+            .acquire(&broker(), || connector.connect())
+            .await
+            .unwrap();
+        assert_eq!(0, id);
+        assert_eq!(1, connector.opened());
+    }
+}
@@ -1,28 +1,39 @@
 use std::{
     collections::HashMap,
+    future::Future,
     pin::Pin,
     sync::{
         atomic::{
-            AtomicBool,
+            AtomicBool, AtomicU64, AtomicU8, AtomicUsize,
             Ordering::{Relaxed, SeqCst},
         },
-        Arc,
+        Arc, Weak,
     },
     task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use rabbitmq_stream_protocol::{
-    commands::subscribe::OffsetSpecification, message::Message, ResponseKind,
+    commands::{deliver::DeliverCommand, subscribe::OffsetSpecification},
+    message::Message,
+    ResponseCode, ResponseKind,
 };
-use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tracing::trace;
+use tokio::sync::{
+    mpsc::{channel, Receiver, Sender},
+    RwLock,
+};
+use tracing::{trace, warn};
 
 use crate::{
-    client::{MessageHandler, MessageResult},
-    error::{ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError},
-    Client, ClientOptions, Environment, MetricsCollector,
+    client::{Broker, MessageHandler, MessageResult},
+    error::{
+        ClientError, ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError,
+        ConsumerStoreOffsetError,
+    },
+    recovery::{self, Migration},
+    Client, CreditStrategy, Environment, MetricsCollector,
 };
-use futures::{task::AtomicWaker, Stream};
+use futures::{future::BoxFuture, task::AtomicWaker, FutureExt, Stream};
 
 use rand::seq::SliceRandom;
 
@@ -32,10 +43,69 @@ pub struct Consumer {
     internal: Arc<ConsumerInternal>,
 }
 
+pub(crate) type ConsumerUpdateListener =
+    Arc<dyn Fn(ConsumerUpdateContext) -> BoxFuture<'static, OffsetSpecification> + Send + Sync>;
+
+/// How a named consumer stores on the server the offset of the messages it consumed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum OffsetTracking {
+    /// Offsets are stored only with [`ConsumerHandle::store_offset`]
+    #[default]
+    Manual,
+    /// Store the offset of the last message taken by the application every `messages`
+    /// messages and/or every `interval`, and when the consumer is closed
+    Automatic {
+        messages: Option<u64>,
+        interval: Option<Duration>,
+    },
+}
+
+/// Where a bounded consumer stops, see [`ConsumerBuilder::until`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum EndSpecification {
+    /// Stop after the message at this offset
+    Offset(u64),
+    /// Stop before the first chunk written after this timestamp, in milliseconds since the epoch
+    Timestamp(i64),
+    /// Stop after the last chunk of the stream when subscribing
+    CurrentTail,
+}
+
+impl OffsetTracking {
+    /// Store the offset every 10,000 messages, every 5 seconds and on close
+    pub fn automatic() -> Self {
+        OffsetTracking::Automatic {
+            messages: Some(10_000),
+            interval: Some(Duration::from_secs(5)),
+        }
+    }
+}
+
 struct ConsumerInternal {
-    client: Client,
+    environment: Environment,
+    client: RwLock<Client>,
     stream: String,
-    subscription_id: u8,
+    name: Option<String>,
+    subscription_id: AtomicU8,
+    offset_specification: OffsetSpecification,
+    /// Where the current subscription starts from, the first chunk can hold earlier messages
+    start: std::sync::Mutex<OffsetSpecification>,
+    properties: HashMap<String, String>,
+    consumer_update_listener: Option<ConsumerUpdateListener>,
+    offset_tracking: OffsetTracking,
+    crc_check: bool,
+    end: Option<ReplayEnd>,
+    initial_credits: u16,
+    credit_strategy: Arc<dyn CreditStrategy>,
+    credits: std::sync::Mutex<Credits>,
+    /// Size of the messages sent to the application and not consumed yet
+    buffered_bytes: AtomicUsize,
+    /// Offset of the last message received from the server, recovery resumes after it
+    last_offset: RwLock<Option<u64>>,
+    /// Offset of the last message taken by the application, the one tracking stores
+    consumed_offset: std::sync::Mutex<Option<u64>>,
+    stored_offset: RwLock<Option<u64>>,
+    consumed_since_store: AtomicU64,
     sender: Sender<Result<Delivery, ConsumerDeliveryError>>,
     closed: Arc<AtomicBool>,
     waker: AtomicWaker,
@@ -46,85 +116,565 @@ impl ConsumerInternal {
     fn is_closed(&self) -> bool {
         self.closed.load(Relaxed)
     }
+
+    async fn client(&self) -> Client {
+        self.client.read().await.clone()
+    }
+
+    fn subscription_id(&self) -> u8 {
+        self.subscription_id.load(SeqCst)
+    }
+
+    /// Give back the subscription id and connection to the pool of the environment
+    async fn release(&self, client: &Client, subscription_id: u8) {
+        client.remove_subscription_handler(subscription_id).await;
+        self.environment
+            .consumer_connections
+            .release(client, subscription_id)
+            .await;
+    }
+
+    /// Register the message handler on `client` and subscribe to the stream
+    async fn attach(
+        self: &Arc<Self>,
+        client: &Client,
+        offset_specification: OffsetSpecification,
+    ) -> Result<(), ConsumerCreateError> {
+        client
+            .set_subscription_handler(self.subscription_id(), ConsumerMessageHandler(self.clone()))
+            .await;
+        self.set_start(offset_specification.clone());
+        let initial_credits = self.credits.lock().unwrap().subscribe(self.initial_credits);
+
+        let response = client
+            .subscribe(
+                self.subscription_id(),
+                &self.stream,
+                offset_specification,
+                initial_credits,
+                self.properties.clone(),
+            )
+            .await?;
+
+        if response.is_ok() {
+            Ok(())
+        } else {
+            Err(ConsumerCreateError::Create {
+                stream: self.stream.clone(),
+                status: response.code().clone(),
+            })
+        }
+    }
+
+    async fn recover(self: Arc<Self>) {
+        self.release(&self.client().await, self.subscription_id())
+            .await;
+
+        let options = &self.environment.options;
+        let recovered = recovery::recover(
+            &options.recovery_policy,
+            options.recovery_listener.as_ref(),
+            &self.stream,
+            &self.closed,
+            || self.reconnect(),
+        )
+        .await;
+
+        if !recovered {
+            self.closed.store(true, Relaxed);
+            self.waker.wake();
+        }
+    }
+
+    /// Subscribe again to a replica of the stream after the server removed the subscription,
+    /// the application gets an error if the stream was deleted
+    async fn migrate(self: Arc<Self>) {
+        // the subscription does not exist anymore, there is nothing to unsubscribe
+        self.release(&self.client().await, self.subscription_id())
+            .await;
+
+        let migration = recovery::migrate(&self.environment, &self.stream, &self.closed, || {
+            self.reconnect()
+        })
+        .await;
+        if migration == Migration::StreamDeleted {
+            let _ = self
+                .sender
+                .send(Err(ConsumerDeliveryError::StreamDeleted {
+                    stream: self.stream.clone(),
+                }))
+                .await;
+        }
+        if migration != Migration::Recovered {
+            self.closed.store(true, Relaxed);
+            self.waker.wake();
+        }
+    }
+
+    async fn reconnect(self: &Arc<Self>) -> Result<(), ConsumerCreateError> {
+        let (client, subscription_id) = connect_to_replica(&self.environment, &self.stream).await?;
+        self.subscription_id.store(subscription_id, SeqCst);
+
+        if let Err(err) = self.attach(&client, self.resume_offset().await).await {
+            self.release(&client, subscription_id).await;
+            return Err(err);
+        }
+
+        // checked under the lock, close uses either the old client or this one
+        let mut current = self.client.write().await;
+        if self.is_closed() {
+            drop(current);
+            trace!("Consumer closed while reconnecting, unsubscribing");
+            let _ = client.unsubscribe(subscription_id).await;
+            self.release(&client, subscription_id).await;
+        } else {
+            *current = client;
+        }
+        Ok(())
+    }
+
+    /// Grant back to the server up to `credits` of the credits it took
+    async fn grant_credits(&self, credits: u16) {
+        let credits = self.credits.lock().unwrap().grant(credits);
+        self.send_credits(credits).await;
+    }
+
+    async fn send_credits(&self, credits: u16) {
+        if credits > 0 {
+            // TODO handle credit fail
+            let _ = self
+                .client()
+                .await
+                .credit(self.subscription_id(), credits)
+                .await;
+        }
+    }
+
+    /// The application consumed the last message of a chunk of `bytes`
+    async fn chunk_consumed(&self, bytes: usize) {
+        let buffered_bytes = self.buffered_bytes.fetch_sub(bytes, SeqCst) - bytes;
+        self.grant_credits(self.credit_strategy.chunk_consumed(buffered_bytes))
+            .await;
+    }
+
+    fn set_start(&self, offset_specification: OffsetSpecification) {
+        *self.start.lock().unwrap() = offset_specification;
+    }
+
+    /// Offset after the last delivered message, or the initial one if nothing was delivered yet
+    async fn resume_offset(&self) -> OffsetSpecification {
+        match *self.last_offset.read().await {
+            Some(offset) => OffsetSpecification::Offset(offset + 1),
+            None => self.offset_specification.clone(),
+        }
+    }
+
+    /// Offset after the one stored on the server for the consumer's name, if any
+    async fn stored_resume_offset(
+        &self,
+        client: &Client,
+    ) -> Result<Option<OffsetSpecification>, ClientError> {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => return Ok(None),
+        };
+        match client.query_offset(name, &self.stream).await {
+            Ok(offset) => Ok(Some(OffsetSpecification::Offset(offset + 1))),
+            Err(ClientError::RequestError(ResponseCode::NoOffset)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Ask the listener where to start from after a consumer update of the server
+    async fn consumer_update(&self, active: bool) -> OffsetSpecification {
+        if !active && self.is_tracking_offsets() {
+            // let the consumer promoted in our place resume from here
+            if let Err(err) = self.flush_offset().await {
+                warn!(
+                    "Failed to store offset of stream {}: {:?}",
+                    self.stream, err
+                );
+            }
+        }
+
+        match &self.consumer_update_listener {
+            Some(listener) => {
+                listener(ConsumerUpdateContext {
+                    active,
+                    name: self.name.clone().unwrap_or_default(),
+                    stream: self.stream.clone(),
+                    client: self.client().await,
+                })
+                .await
+            }
+            None if active => match self.stored_resume_offset(&self.client().await).await {
+                Ok(Some(offset_specification)) => offset_specification,
+                _ => self.resume_offset().await,
+            },
+            None => self.resume_offset().await,
+        }
+    }
+
+    fn is_tracking_offsets(&self) -> bool {
+        self.name.is_some() && matches!(self.offset_tracking, OffsetTracking::Automatic { .. })
+    }
+
+    async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        let name = self
+            .name
+            .as_ref()
+            .ok_or_else(|| ConsumerStoreOffsetError::NameMissing {
+                stream: self.stream.clone(),
+            })?;
+
+        self.client()
+            .await
+            .store_offset(name, &self.stream, offset)
+            .await?;
+        *self.stored_offset.write().await = Some(offset);
+        self.consumed_since_store.store(0, Relaxed);
+        Ok(())
+    }
+
+    /// Store the offset of the last consumed message, unless it is already stored
+    async fn flush_offset(&self) -> Result<(), ConsumerStoreOffsetError> {
+        let consumed_offset = *self.consumed_offset.lock().unwrap();
+        match consumed_offset {
+            Some(offset) if *self.stored_offset.read().await != Some(offset) => {
+                self.store_offset(offset).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The application took the message at `offset`, tell if automatic tracking
+    /// reached its threshold and has to store it
+    fn track_consumed(&self, offset: u64) -> bool {
+        *self.consumed_offset.lock().unwrap() = Some(offset);
+        if !self.is_tracking_offsets() {
+            return false;
+        }
+        let total = self.consumed_since_store.fetch_add(1, Relaxed) + 1;
+        match self.offset_tracking {
+            OffsetTracking::Automatic {
+                messages: Some(messages),
+                ..
+            } if total >= messages => {
+                self.consumed_since_store.store(0, Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    async fn track_offset(&self, offset: u64) {
+        let mut last_offset = self.last_offset.write().await;
+        if !matches!(*last_offset, Some(last) if last >= offset) {
+            *last_offset = Some(offset);
+        }
+    }
+}
+
+/// State passed to the consumer update listener, see [`ConsumerBuilder::consumer_update`]
+pub struct ConsumerUpdateContext {
+    active: bool,
+    name: String,
+    stream: String,
+    client: Client,
+}
+
+impl ConsumerUpdateContext {
+    /// Check if the consumer was promoted to active, otherwise it was demoted
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Get a reference to the consumer's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get a reference to the consumer's stream.
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// Client of the consumer, e.g. for querying the stored offset
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
 }
 
 /// Builder for [`Consumer`]
 pub struct ConsumerBuilder {
     pub environment: Environment,
     pub offset_specification: OffsetSpecification,
+    pub name: Option<String>,
+    pub offset_tracking: OffsetTracking,
+    pub single_active_consumer: bool,
+    pub crc_check: bool,
+    pub end_specification: Option<EndSpecification>,
+    pub initial_credits: u16,
+    pub buffer_size: usize,
+    pub credit_strategy: Arc<dyn CreditStrategy>,
+    pub(crate) super_stream: Option<String>,
+    pub(crate) consumer_update_listener: Option<ConsumerUpdateListener>,
 }
 
 impl ConsumerBuilder {
     pub async fn build(self, stream: &str) -> Result<Consumer, ConsumerCreateError> {
-        // Connect to the user specified node first, then look for a random replica to connect to instead.
-        // This is recommended for load balancing purposes.
-        let mut client = self.environment.create_client().await?;
+        let mut properties = HashMap::new();
+        if self.single_active_consumer {
+            match &self.name {
+                Some(name) => properties.insert("name".to_owned(), name.clone()),
+                None => {
+                    return Err(ConsumerCreateError::SingleActiveConsumerWithoutName {
+                        stream: stream.to_owned(),
+                    })
+                }
+            };
+            properties.insert("single-active-consumer".to_owned(), "true".to_owned());
+            if let Some(super_stream) = &self.super_stream {
+                properties.insert("super-stream".to_owned(), super_stream.clone());
+            }
+        }
+
+        let (client, subscription_id) = connect_to_replica(&self.environment, stream).await?;
+        let end = match &self.end_specification {
+            Some(end_specification) => {
+                match ReplayEnd::resolve(&client, stream, end_specification).await {
+                    Ok(end) => Some(end),
+                    Err(err) => {
+                        self.environment
+                            .consumer_connections
+                            .release(&client, subscription_id)
+                            .await;
+                        return Err(err.into());
+                    }
+                }
+            }
+            None => None,
+        };
         let collector = self.environment.options.client_options.collector.clone();
-        if let Some(metadata) = client.metadata(vec![stream.to_string()]).await?.get(stream) {
-            // If there are no replicas we do not reassign client, meaning we just keep reading from the leader.
-            // This is desired behavior in case there is only one node in the cluster.
-            if let Some(replica) = metadata.replicas.choose(&mut rand::rngs::OsRng) {
+        let interval = match self.offset_tracking {
+            OffsetTracking::Automatic { interval, .. } if self.name.is_some() => interval,
+            _ => None,
+        };
+
+        let (tx, rx) = channel(self.buffer_size.max(1));
+        let consumer = Arc::new(ConsumerInternal {
+            environment: self.environment,
+            subscription_id: AtomicU8::new(subscription_id),
+            stream: stream.to_string(),
+            name: self.name,
+            offset_specification: self.offset_specification.clone(),
+            start: std::sync::Mutex::new(self.offset_specification.clone()),
+            properties,
+            consumer_update_listener: self.consumer_update_listener,
+            offset_tracking: self.offset_tracking,
+            crc_check: self.crc_check,
+            end,
+            initial_credits: self.initial_credits,
+            credit_strategy: self.credit_strategy,
+            credits: std::sync::Mutex::new(Credits::default()),
+            buffered_bytes: AtomicUsize::new(0),
+            last_offset: RwLock::new(None),
+            consumed_offset: std::sync::Mutex::new(None),
+            stored_offset: RwLock::new(None),
+            consumed_since_store: AtomicU64::new(0),
+            client: RwLock::new(client.clone()),
+            sender: tx,
+            closed: Arc::new(AtomicBool::new(false)),
+            waker: AtomicWaker::new(),
+            metrics_collector: collector,
+        });
+
+        // a named consumer resumes after the offset stored by its previous run
+        let attached = match consumer.stored_resume_offset(&client).await {
+            Ok(stored) => {
+                let offset_specification = stored.unwrap_or(self.offset_specification);
+                consumer.attach(&client, offset_specification).await
+            }
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = attached {
+            consumer.release(&client, subscription_id).await;
+            return Err(err);
+        }
+
+        if let Some(interval) = interval {
+            schedule_offset_flush(Arc::downgrade(&consumer), interval);
+        }
+
+        if matches!(&consumer.end, Some(end) if end.empty) {
+            trace!("Nothing to consume before the end of stream {}", stream);
+            let _ = ConsumerHandle(consumer.clone()).close().await;
+        }
+
+        Ok(Consumer {
+            receiver: rx,
+            internal: consumer,
+        })
+    }
+
+    /// Where consuming starts from.
+    ///
+    /// A named consumer with an offset stored on the server resumes after
+    /// the stored offset instead, see [`ConsumerBuilder::name`].
+    pub fn offset(mut self, offset_specification: OffsetSpecification) -> Self {
+        self.offset_specification = offset_specification;
+        self
+    }
+
+    /// Name of the consumer, its offset is stored on the server under this name.
+    ///
+    /// When an offset is stored for the name, the consumer resumes after it and
+    /// [`ConsumerBuilder::offset`] is only used when none is stored.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// How the offset of a named consumer is stored, [`OffsetTracking::Manual`] if not set
+    pub fn offset_tracking(mut self, offset_tracking: OffsetTracking) -> Self {
+        self.offset_tracking = offset_tracking;
+        self
+    }
+
+    /// Only one of the consumers with the same name consumes the stream at a time,
+    /// requires a [`ConsumerBuilder::name`]
+    pub fn single_active_consumer(mut self, single_active_consumer: bool) -> Self {
+        self.single_active_consumer = single_active_consumer;
+        self
+    }
+
+    /// Stop consuming at `end_specification`, the consumer is then closed
+    /// and its stream of deliveries ends
+    pub fn until(mut self, end_specification: EndSpecification) -> Self {
+        self.end_specification = Some(end_specification);
+        self
+    }
+
+    /// Verify the CRC32 of the data of each chunk, the messages of a corrupted chunk
+    /// are not delivered and [`ConsumerDeliveryError::Crc`] is returned instead.
+    ///
+    /// Chunks whose entries cannot be read are reported this way even without the check.
+    pub fn crc_check(mut self, crc_check: bool) -> Self {
+        self.crc_check = crc_check;
+        self
+    }
+
+    /// Credits given to the server when subscribing, the number of chunks
+    /// it can deliver before the consumer grants more
+    pub fn initial_credits(mut self, initial_credits: u16) -> Self {
+        self.initial_credits = initial_credits;
+        self
+    }
+
+    /// Number of messages waiting to be consumed by the application before
+    /// the reception of more messages waits
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// When the consumer grants the server credits for more chunks,
+    /// [`crate::ChunkEnqueuedCredit`] if not set
+    pub fn credit_strategy(mut self, credit_strategy: impl CreditStrategy + 'static) -> Self {
+        self.credit_strategy = Arc::new(credit_strategy);
+        self
+    }
+
+    /// Listener called when the server promotes or demotes this single active consumer.
+    ///
+    /// On promotion the returned [`OffsetSpecification`] is where consuming starts from.
+    /// Without a listener the consumer resumes after the last message it received,
+    /// or from [`ConsumerBuilder::offset`] if it did not receive any.
+    pub fn consumer_update<Fut>(
+        mut self,
+        listener: impl Fn(ConsumerUpdateContext) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        Fut: Future<Output = OffsetSpecification> + Send + 'static,
+    {
+        self.consumer_update_listener = Some(Arc::new(move |context| listener(context).boxed()));
+        self
+    }
+}
+
+/// Connect to the user specified node first, then look for a random replica to connect to instead.
+/// This is recommended for load balancing purposes.
+///
+/// The connection comes from the pool of the environment, along with a free subscription id.
+async fn connect_to_replica(
+    environment: &Environment,
+    stream: &str,
+) -> Result<(Client, u8), ConsumerCreateError> {
+    let client = environment.create_client().await?;
+    let replica = match client.metadata(vec![stream.to_string()]).await?.get(stream) {
+        Some(metadata) => metadata
+            .replicas
+            .choose(&mut rand::rngs::OsRng)
+            .map(|replica| {
                 tracing::debug!(
                     "Picked replica {:?} out of possible candidates {:?} for stream {}",
                     replica,
                     metadata.replicas,
                     stream
                 );
-                client = Client::connect(ClientOptions {
-                    host: replica.host.clone(),
-                    port: replica.port as u16,
-                    ..self.environment.options.client_options
-                })
-                .await?;
-            }
-        } else {
+                replica.clone()
+            }),
+        None => {
+            client.close().await?;
             return Err(ConsumerCreateError::StreamDoesNotExist {
                 stream: stream.into(),
             });
         }
+    };
+    let node = client.options().clone();
+    client.close().await?;
 
-        let subscription_id = 1;
-        let response = client
-            .subscribe(
-                subscription_id,
-                stream,
-                self.offset_specification,
-                1,
-                HashMap::new(),
-            )
-            .await?;
+    let connections = &environment.consumer_connections;
+    Ok(match replica {
+        Some(replica) => {
+            connections
+                .acquire(&replica, || environment.connect_to_broker(&replica))
+                .await?
+        }
+        // If there are no replicas we keep reading from the user specified node which answered.
+        // This is desired behavior in case there is only one node in the cluster.
+        None => {
+            let broker = Broker {
+                host: node.host.clone(),
+                port: node.port as u32,
+            };
+            connections
+                .acquire(&broker, || Client::connect(node))
+                .await?
+        }
+    })
+}
 
-        if response.is_ok() {
-            let (tx, rx) = channel(10000);
-            let consumer = Arc::new(ConsumerInternal {
-                subscription_id,
-                stream: stream.to_string(),
-                client: client.clone(),
-                sender: tx,
-                closed: Arc::new(AtomicBool::new(false)),
-                waker: AtomicWaker::new(),
-                metrics_collector: collector,
-            });
+fn schedule_offset_flush(consumer: Weak<ConsumerInternal>, period: Duration) {
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.tick().await;
 
-            let msg_handler = ConsumerMessageHandler(consumer.clone());
-            client.set_handler(msg_handler).await;
+        loop {
+            interval.tick().await;
 
-            Ok(Consumer {
-                receiver: rx,
-                internal: consumer,
-            })
-        } else {
-            Err(ConsumerCreateError::Create {
-                stream: stream.to_owned(),
-                status: response.code().clone(),
-            })
+            let consumer = match consumer.upgrade() {
+                Some(consumer) if !consumer.is_closed() => consumer,
+                _ => break,
+            };
+            if let Err(err) = consumer.flush_offset().await {
+                warn!(
+                    "Failed to store offset of stream {}: {:?}",
+                    consumer.stream, err
+                );
+            }
         }
-    }
-
-    pub fn offset(mut self, offset_specification: OffsetSpecification) -> Self {
-        self.offset_specification = offset_specification;
-        self
-    }
+    });
 }
 
 impl Consumer {
@@ -137,6 +687,55 @@ impl Consumer {
     pub fn is_closed(&self) -> bool {
         self.internal.is_closed()
     }
+
+    /// Store `offset` on the server under the consumer's name
+    pub async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        self.internal.store_offset(offset).await
+    }
+
+    /// Consume the messages a whole chunk at a time
+    pub fn chunks(self) -> ConsumerChunks {
+        ConsumerChunks {
+            consumer: self,
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// Stream of the deliveries of a [`Consumer`] grouped by chunk, see [`Consumer::chunks`]
+pub struct ConsumerChunks {
+    consumer: Consumer,
+    pending: Vec<Delivery>,
+}
+
+impl ConsumerChunks {
+    /// Return an handle for the underlying [`Consumer`]
+    pub fn handle(&self) -> ConsumerHandle {
+        self.consumer.handle()
+    }
+}
+
+impl Stream for ConsumerChunks {
+    type Item = Result<Vec<Delivery>, ConsumerDeliveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match Pin::new(&mut self.consumer).poll_next(cx) {
+                Poll::Ready(Some(Ok(delivery))) => {
+                    let last_in_chunk = delivery.chunk_end.is_some();
+                    self.pending.push(delivery);
+                    if last_in_chunk {
+                        return Poll::Ready(Some(Ok(std::mem::take(&mut self.pending))));
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) if !self.pending.is_empty() => {
+                    return Poll::Ready(Some(Ok(std::mem::take(&mut self.pending))))
+                }
+                poll => return poll.map(|_| None),
+            }
+        }
+    }
 }
 
 impl Stream for Consumer {
@@ -145,6 +744,24 @@ impl Stream for Consumer {
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         self.internal.waker.register(cx.waker());
         let poll = Pin::new(&mut self.receiver).poll_recv(cx);
+        if let Poll::Ready(Some(Ok(delivery))) = &poll {
+            if let Some(bytes) = delivery.chunk_end {
+                let internal = self.internal.clone();
+                tokio::task::spawn(async move { internal.chunk_consumed(bytes).await });
+            }
+            // the offset is tracked once the application has the message, not when received
+            if self.internal.track_consumed(delivery.offset) {
+                let internal = self.internal.clone();
+                tokio::task::spawn(async move {
+                    if let Err(err) = internal.flush_offset().await {
+                        warn!(
+                            "Failed to store offset of stream {}: {:?}",
+                            internal.stream, err
+                        );
+                    }
+                });
+            }
+        }
         match (self.is_closed(), poll.is_ready()) {
             (true, false) => Poll::Ready(None),
             _ => poll,
@@ -153,6 +770,7 @@ impl Stream for Consumer {
 }
 
 /// Handler API for [`Consumer`]
+#[derive(Clone)]
 pub struct ConsumerHandle(Arc<ConsumerInternal>);
 
 impl ConsumerHandle {
@@ -160,10 +778,22 @@ impl ConsumerHandle {
     pub async fn close(self) -> Result<(), ConsumerCloseError> {
         match self.0.closed.compare_exchange(false, true, SeqCst, SeqCst) {
             Ok(false) => {
-                let response = self.0.client.unsubscribe(self.0.subscription_id).await?;
+                if self.0.is_tracking_offsets() {
+                    if let Err(err) = self.0.flush_offset().await {
+                        warn!(
+                            "Failed to store offset of stream {}: {:?}",
+                            self.0.stream, err
+                        );
+                    }
+                }
+                let client = self.0.client().await;
+                let subscription_id = self.0.subscription_id();
+                let response = client.unsubscribe(subscription_id).await;
+                self.0.release(&client, subscription_id).await;
+                self.0.waker.wake();
+
+                let response = response?;
                 if response.is_ok() {
-                    self.0.waker.wake();
-                    self.0.client.close().await.expect("close client failed");
                     Ok(())
                 } else {
                     Err(ConsumerCloseError::Close {
@@ -179,6 +809,28 @@ impl ConsumerHandle {
     pub async fn is_closed(&self) -> bool {
         self.0.is_closed()
     }
+
+    /// Stop granting the server credits for more chunks, the chunks already
+    /// granted are still delivered
+    pub fn pause(&self) {
+        self.0.credits.lock().unwrap().pause();
+    }
+
+    /// Grant the server the credits held back while paused
+    pub async fn resume(&self) {
+        let credits = self.0.credits.lock().unwrap().resume();
+        self.0.send_credits(credits).await;
+    }
+
+    /// Check if the consumer is paused
+    pub fn is_paused(&self) -> bool {
+        self.0.credits.lock().unwrap().paused
+    }
+
+    /// Store `offset` on the server under the consumer's name
+    pub async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        self.0.store_offset(offset).await
+    }
 }
 
 struct ConsumerMessageHandler(Arc<ConsumerInternal>);
@@ -188,56 +840,284 @@ impl MessageHandler for ConsumerMessageHandler {
     async fn handle_message(&self, item: MessageResult) -> crate::RabbitMQStreamResult<()> {
         match item {
             Some(Ok(response)) => {
-                let kind =  response.kind();
+                let kind = response.kind();
                 if let ResponseKind::Deliver(delivery) = kind {
-                    let mut offset = delivery.chunk_first_offset;
+                    trace!("Got delivery with messages {}", delivery.messages.len());
+                    let start = self.0.start.lock().unwrap().clone();
+                    let chunk_id = delivery.chunk_first_offset;
+                    let chunk_timestamp = UNIX_EPOCH + Duration::from_millis(delivery.timestamp());
+                    let epoch = delivery.epoch();
+                    let messages = if delivery.is_corrupted()
+                        || (self.0.crc_check && !delivery.is_crc_valid())
+                    {
+                        warn!(
+                            "CRC of chunk {} of stream {} does not match",
+                            chunk_id, self.0.stream
+                        );
+                        self.0.metrics_collector.crc_error(1).await;
+                        let _ = self
+                            .0
+                            .sender
+                            .send(Err(ConsumerDeliveryError::Crc { chunk_id }))
+                            .await;
+                        vec![]
+                    } else {
+                        messages_from(&start, delivery)
+                    };
+                    let (messages, ended) = match &self.0.end {
+                        Some(end) => end.bound(chunk_id, chunk_timestamp, messages),
+                        None => (messages, false),
+                    };
+                    let len = messages.len();
+                    let bytes = messages
+                        .iter()
+                        .map(|(_, message)| message.data().map_or(0, |data| data.len()))
+                        .sum();
+                    self.0.credits.lock().unwrap().take();
+                    self.0.buffered_bytes.fetch_add(bytes, SeqCst);
 
-                    let len = delivery.messages.len();
-                    trace!("Got delivery with messages {}", len);
-                    for message in delivery.messages {
+                    for (index, (offset, message)) in messages.into_iter().enumerate() {
                         let _ = self
                             .0
                             .sender
                             .send(Ok(Delivery {
-                                subscription_id: self.0.subscription_id,
+                                stream: self.0.stream.clone(),
+                                subscription_id: self.0.subscription_id(),
                                 message,
                                 offset,
+                                chunk_id,
+                                chunk_timestamp,
+                                epoch,
+                                chunk_end: (index + 1 == len).then_some(bytes),
                             }))
                             .await;
-                        offset += 1;
+                        self.0.track_offset(offset).await;
                     }
 
-                    // TODO handle credit fail
-                    let _ = self.0.client.credit(self.0.subscription_id, 1).await;
+                    if len == 0 {
+                        // nothing for the application to consume
+                        self.0.chunk_consumed(bytes).await;
+                    }
+                    let buffered_bytes = self.0.buffered_bytes.load(SeqCst);
+                    self.0
+                        .grant_credits(self.0.credit_strategy.chunk_enqueued(buffered_bytes))
+                        .await;
                     self.0.metrics_collector.consume(len as u64).await;
-                }else if  let ResponseKind::Heartbeat(_) = kind  {
-                    // credit on heartbeat
-                    let _ = self.0.client.credit(self.0.subscription_id, 1).await;
-                }
-                else{
-                    println!("Response kind {:?}", kind);
+
+                    if ended && !self.0.is_closed() {
+                        trace!("End of stream {} reached, closing consumer", self.0.stream);
+                        let _ = ConsumerHandle(self.0.clone()).close().await;
+                    }
+                } else if let ResponseKind::ConsumerUpdate(update) = kind {
+                    trace!("Got consumer update, active: {}", update.is_active());
+                    let offset_specification = self.0.consumer_update(update.is_active()).await;
+                    self.0.set_start(offset_specification.clone());
+                    let _ = self
+                        .0
+                        .client()
+                        .await
+                        .consumer_update(update.correlation_id, offset_specification)
+                        .await;
+                } else if let ResponseKind::MetadataUpdate(update) = kind {
+                    if update.stream() == self.0.stream && !self.0.is_closed() {
+                        trace!("Stream {} moved, migrating consumer", update.stream());
+                        self.0.clone().migrate().await;
+                    }
+                } else {
+                    trace!("Unexpected response {:?}", kind);
                 }
             }
             Some(Err(err)) => {
                 let _ = self.0.sender.send(Err(err.into())).await;
             }
             None => {
-                println!("Closing consumer");
-                self.0.closed.store(true, Relaxed);
-                self.0.waker.wake();
+                if self.0.is_closed() {
+                    trace!("Closing consumer");
+                    self.0.waker.wake();
+                } else {
+                    trace!("Connection lost, recovering consumer");
+                    self.0.clone().recover().await;
+                }
             }
         }
         Ok(())
     }
 }
+/// Last message of a bounded consumer, resolved from its [`EndSpecification`]
+#[derive(Debug, Default, PartialEq)]
+struct ReplayEnd {
+    /// Offset of the last message to deliver
+    offset: Option<u64>,
+    /// Id of the last chunk to deliver
+    chunk_id: Option<u64>,
+    /// Chunks written after it are not delivered
+    timestamp: Option<SystemTime>,
+    /// Nothing to deliver, the stream was empty when subscribing
+    empty: bool,
+}
+
+impl ReplayEnd {
+    async fn resolve(
+        client: &Client,
+        stream: &str,
+        end_specification: &EndSpecification,
+    ) -> Result<ReplayEnd, ClientError> {
+        match end_specification {
+            EndSpecification::Offset(offset) => Ok(ReplayEnd {
+                offset: Some(*offset),
+                ..Default::default()
+            }),
+            EndSpecification::Timestamp(timestamp) => {
+                let timestamp = UNIX_EPOCH + Duration::from_millis((*timestamp).max(0) as u64);
+                // no chunk written after a past timestamp may come, stop at the tail instead
+                let tail = if timestamp <= SystemTime::now() {
+                    Self::tail(client, stream).await?
+                } else {
+                    ReplayEnd::default()
+                };
+                Ok(ReplayEnd {
+                    timestamp: Some(timestamp),
+                    ..tail
+                })
+            }
+            EndSpecification::CurrentTail => Self::tail(client, stream).await,
+        }
+    }
+
+    /// End at the last chunk committed in `stream`
+    async fn tail(client: &Client, stream: &str) -> Result<ReplayEnd, ClientError> {
+        let stats = client.stream_stats(stream).await?;
+        match stats.get("committed_chunk_id") {
+            Some(chunk_id) if *chunk_id >= 0 => Ok(ReplayEnd {
+                chunk_id: Some(*chunk_id as u64),
+                ..Default::default()
+            }),
+            _ => Ok(ReplayEnd {
+                empty: true,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Keep the `messages` of a chunk up to the end, and tell if the end is reached
+    fn bound(
+        &self,
+        chunk_id: u64,
+        timestamp: SystemTime,
+        messages: Vec<(u64, Message)>,
+    ) -> (Vec<(u64, Message)>, bool) {
+        let after_end = self.empty
+            || matches!(self.chunk_id, Some(last) if chunk_id > last)
+            || matches!(self.timestamp, Some(last) if timestamp > last);
+        if after_end {
+            return (vec![], true);
+        }
+
+        let mut ended = matches!(self.chunk_id, Some(last) if chunk_id == last);
+        let messages = match self.offset {
+            Some(last) => {
+                ended |= chunk_id > last || messages.iter().any(|(offset, _)| *offset >= last);
+                messages
+                    .into_iter()
+                    .filter(|(offset, _)| *offset <= last)
+                    .collect()
+            }
+            None => messages,
+        };
+        (messages, ended)
+    }
+}
+
+/// Credits of a subscription, held back while the consumer is paused
+#[derive(Debug, Default)]
+struct Credits {
+    /// Taken by the server for the chunks it delivered and not granted back yet
+    taken: u16,
+    /// Granted while paused, sent to the server on resume
+    held: u16,
+    paused: bool,
+}
+
+impl Credits {
+    /// The server took a credit to deliver a chunk
+    fn take(&mut self) {
+        self.taken = self.taken.saturating_add(1);
+    }
+
+    /// Credits to send to the server out of the `credits` the strategy grants
+    fn grant(&mut self, credits: u16) -> u16 {
+        let credits = credits.min(self.taken);
+        self.taken -= credits;
+        if self.paused {
+            self.held = self.held.saturating_add(credits);
+            0
+        } else {
+            credits
+        }
+    }
+
+    /// Credits to subscribe with, held back if paused
+    fn subscribe(&mut self, initial_credits: u16) -> u16 {
+        self.taken = 0;
+        if self.paused {
+            self.held = initial_credits;
+            0
+        } else {
+            self.held = 0;
+            initial_credits
+        }
+    }
+
+    fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Credits held back while paused
+    fn resume(&mut self) -> u16 {
+        self.paused = false;
+        std::mem::take(&mut self.held)
+    }
+}
+
+/// Offsets and messages of `delivery` starting from `start`.
+///
+/// The server sends the whole chunk holding the requested offset or timestamp,
+/// so the messages of the first chunk before it are skipped.
+fn messages_from(start: &OffsetSpecification, delivery: DeliverCommand) -> Vec<(u64, Message)> {
+    if let OffsetSpecification::Timestamp(timestamp) = start {
+        if (delivery.timestamp() as i64) < *timestamp {
+            return vec![];
+        }
+    }
+    let first_offset = match start {
+        OffsetSpecification::Offset(offset) => *offset,
+        _ => 0,
+    };
+    (delivery.chunk_first_offset..)
+        .zip(delivery.messages)
+        .filter(|(offset, _)| *offset >= first_offset)
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct Delivery {
+    pub stream: String,
     pub subscription_id: u8,
     pub message: Message,
     pub offset: u64,
+    pub chunk_id: u64,
+    pub chunk_timestamp: SystemTime,
+    pub epoch: u64,
+    /// Size of the messages of the chunk, set on its last message
+    pub(crate) chunk_end: Option<usize>,
 }
 
 impl Delivery {
+    /// Get a reference to the stream the message was read from.
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
     /// Get a reference to the delivery's subscription id.
     pub fn subscription_id(&self) -> u8 {
         self.subscription_id
@@ -252,4 +1132,203 @@ impl Delivery {
     pub fn offset(&self) -> u64 {
         self.offset
     }
+
+    /// Offset of the first message of the chunk holding the message
+    pub fn chunk_id(&self) -> u64 {
+        self.chunk_id
+    }
+
+    /// Time the chunk holding the message was written by the server
+    pub fn chunk_timestamp(&self) -> SystemTime {
+        self.chunk_timestamp
+    }
+
+    /// Epoch of the stream leader that wrote the chunk holding the message
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rabbitmq_stream_protocol::{
+        commands::{deliver::DeliverCommand, subscribe::OffsetSpecification},
+        message::Message,
+    };
+
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{messages_from, Credits, ReplayEnd};
+
+    fn chunk(timestamp: u64, chunk_first_offset: u64, count: usize) -> DeliverCommand {
+        let messages = (0..count)
+            .map(|i| Message::builder().body(vec![i as u8]).build())
+            .collect();
+        DeliverCommand::new(
+            0,
+            1,
+            0,
+            count as u16,
+            timestamp,
+            0,
+            chunk_first_offset,
+            0,
+            0,
+            0,
+            messages,
+        )
+    }
+
+    fn offsets(start: OffsetSpecification, delivery: DeliverCommand) -> Vec<u64> {
+        messages_from(&start, delivery)
+            .into_iter()
+            .map(|(offset, _)| offset)
+            .collect()
+    }
+
+    #[test]
+    fn messages_from_offset_test() {
+        assert_eq!(
+            vec![12, 13, 14],
+            offsets(OffsetSpecification::Offset(12), chunk(0, 10, 5))
+        );
+        assert_eq!(
+            vec![10, 11],
+            offsets(OffsetSpecification::Offset(3), chunk(0, 10, 2))
+        );
+        assert!(offsets(OffsetSpecification::Offset(20), chunk(0, 10, 5)).is_empty());
+    }
+
+    #[test]
+    fn messages_from_offset_keeps_message_test() {
+        let messages = messages_from(&OffsetSpecification::Offset(11), chunk(0, 10, 3));
+
+        assert_eq!(2, messages.len());
+        assert_eq!(
+            (11, Some([1u8].as_ref())),
+            (messages[0].0, messages[0].1.data())
+        );
+        assert_eq!(
+            (12, Some([2u8].as_ref())),
+            (messages[1].0, messages[1].1.data())
+        );
+    }
+
+    #[test]
+    fn messages_from_timestamp_test() {
+        assert!(offsets(OffsetSpecification::Timestamp(1_000), chunk(999, 0, 3)).is_empty());
+        assert_eq!(
+            vec![0, 1, 2],
+            offsets(OffsetSpecification::Timestamp(1_000), chunk(1_000, 0, 3))
+        );
+    }
+
+    #[test]
+    fn messages_from_other_specifications_test() {
+        for start in [
+            OffsetSpecification::First,
+            OffsetSpecification::Last,
+            OffsetSpecification::Next,
+        ] {
+            assert_eq!(vec![5, 6], offsets(start, chunk(0, 5, 2)));
+        }
+    }
+
+    #[test]
+    fn credits_test() {
+        let mut credits = Credits::default();
+        assert_eq!(2, credits.subscribe(2));
+
+        credits.take();
+        assert_eq!(1, credits.grant(u16::MAX));
+        assert_eq!(0, credits.grant(1));
+    }
+
+    #[test]
+    fn credits_paused_test() {
+        let mut credits = Credits::default();
+        credits.subscribe(1);
+        credits.pause();
+
+        credits.take();
+        credits.take();
+        assert_eq!(0, credits.grant(1));
+        assert_eq!(0, credits.grant(1));
+        assert_eq!(0, credits.grant(1));
+
+        assert_eq!(2, credits.resume());
+        assert_eq!(0, credits.resume());
+
+        credits.take();
+        assert_eq!(1, credits.grant(1));
+    }
+
+    #[test]
+    fn credits_subscribe_paused_test() {
+        let mut credits = Credits::default();
+        credits.pause();
+
+        assert_eq!(0, credits.subscribe(5));
+        assert_eq!(5, credits.resume());
+    }
+
+    fn at(millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    fn bounded(end: &ReplayEnd, delivery: DeliverCommand) -> (Vec<u64>, bool) {
+        let (chunk_id, timestamp) = (delivery.chunk_first_offset, at(delivery.timestamp()));
+        let messages = messages_from(&OffsetSpecification::First, delivery);
+        let (messages, ended) = end.bound(chunk_id, timestamp, messages);
+        (
+            messages.into_iter().map(|(offset, _)| offset).collect(),
+            ended,
+        )
+    }
+
+    #[test]
+    fn replay_end_offset_test() {
+        let end = ReplayEnd {
+            offset: Some(12),
+            ..Default::default()
+        };
+
+        assert_eq!((vec![5, 6], false), bounded(&end, chunk(0, 5, 2)));
+        assert_eq!((vec![10, 11, 12], true), bounded(&end, chunk(0, 10, 5)));
+        assert_eq!((vec![12], true), bounded(&end, chunk(0, 12, 1)));
+        assert_eq!((vec![], true), bounded(&end, chunk(0, 13, 1)));
+    }
+
+    #[test]
+    fn replay_end_chunk_test() {
+        let end = ReplayEnd {
+            chunk_id: Some(10),
+            ..Default::default()
+        };
+
+        assert_eq!((vec![5, 6], false), bounded(&end, chunk(0, 5, 2)));
+        assert_eq!((vec![10, 11, 12], true), bounded(&end, chunk(0, 10, 3)));
+        assert_eq!((vec![], true), bounded(&end, chunk(0, 13, 1)));
+    }
+
+    #[test]
+    fn replay_end_timestamp_test() {
+        let end = ReplayEnd {
+            timestamp: Some(at(1_000)),
+            ..Default::default()
+        };
+
+        assert_eq!((vec![0, 1], false), bounded(&end, chunk(1_000, 0, 2)));
+        assert_eq!((vec![], true), bounded(&end, chunk(1_001, 2, 2)));
+    }
+
+    #[test]
+    fn replay_end_empty_test() {
+        let end = ReplayEnd {
+            empty: true,
+            ..Default::default()
+        };
+
+        assert_eq!((vec![], true), bounded(&end, chunk(0, 0, 2)));
+    }
 }
@@ -0,0 +1,87 @@
+/// When a consumer grants the server credits for more chunks.
+///
+/// The server takes one credit for each chunk it delivers, the credits returned by the
+/// strategy are capped to the ones taken and not granted back yet, so returning
+/// [`u16::MAX`] grants back all of them.
+pub trait CreditStrategy: Send + Sync {
+    /// Credits to grant once a chunk is added to the buffer of the consumer,
+    /// which holds `buffered_bytes` of messages afterwards
+    fn chunk_enqueued(&self, buffered_bytes: usize) -> u16;
+
+    /// Credits to grant once the application consumed the last message of a chunk,
+    /// leaving `buffered_bytes` of messages in the buffer
+    fn chunk_consumed(&self, buffered_bytes: usize) -> u16;
+}
+
+/// Grant a credit as soon as a chunk is added to the buffer, the default strategy
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChunkEnqueuedCredit;
+
+impl CreditStrategy for ChunkEnqueuedCredit {
+    fn chunk_enqueued(&self, _buffered_bytes: usize) -> u16 {
+        1
+    }
+
+    fn chunk_consumed(&self, _buffered_bytes: usize) -> u16 {
+        0
+    }
+}
+
+/// Grant a credit once the application consumed all the messages of a chunk
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChunkConsumedCredit;
+
+impl CreditStrategy for ChunkConsumedCredit {
+    fn chunk_enqueued(&self, _buffered_bytes: usize) -> u16 {
+        0
+    }
+
+    fn chunk_consumed(&self, _buffered_bytes: usize) -> u16 {
+        1
+    }
+}
+
+/// Grant credits while the messages waiting in the buffer take less than `max_bytes`
+#[derive(Clone, Copy, Debug)]
+pub struct ByteBudgetCredit {
+    pub max_bytes: usize,
+}
+
+impl ByteBudgetCredit {
+    pub fn new(max_bytes: usize) -> Self {
+        ByteBudgetCredit { max_bytes }
+    }
+}
+
+impl CreditStrategy for ByteBudgetCredit {
+    fn chunk_enqueued(&self, buffered_bytes: usize) -> u16 {
+        if buffered_bytes < self.max_bytes {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn chunk_consumed(&self, buffered_bytes: usize) -> u16 {
+        if buffered_bytes < self.max_bytes {
+            u16::MAX
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ByteBudgetCredit, CreditStrategy};
+
+    #[test]
+    fn byte_budget_credit_test() {
+        let strategy = ByteBudgetCredit::new(1024);
+
+        assert_eq!(1, strategy.chunk_enqueued(512));
+        assert_eq!(0, strategy.chunk_enqueued(1024));
+        assert_eq!(0, strategy.chunk_consumed(2048));
+        assert_eq!(u16::MAX, strategy.chunk_consumed(1023));
+    }
+}
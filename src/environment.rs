@@ -1,22 +1,39 @@
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::time::Duration;
 
+use rand::seq::SliceRandom;
+use tracing::{trace, warn};
+
 use crate::producer::NoDedup;
-use crate::types::OffsetSpecification;
+use crate::types::{Compression, OffsetSpecification};
 
 use crate::{
-    client::{Client, ClientOptions, MetricsCollector},
-    consumer::ConsumerBuilder,
-    error::StreamDeleteError,
+    client::{
+        Broker, Client, ClientOptions, ConnectionListener, MetricsCollector, TlsConfiguration,
+    },
+    connection_pool::ConnectionPool,
+    consumer::{ConsumerBuilder, OffsetTracking},
+    credit::ChunkEnqueuedCredit,
+    error::{ClientError, StreamDeleteError},
     producer::ProducerBuilder,
+    recovery::{NopRecoveryListener, RecoveryListener, RecoveryPolicy},
+    resolver::AddressResolver,
     stream_creator::StreamCreator,
+    superstream::RoutingStrategy,
+    superstream_consumer::SuperStreamConsumerBuilder,
+    superstream_producer::SuperStreamProducerBuilder,
     RabbitMQStreamResult,
 };
 /// Main access point to a node
 #[derive(Clone)]
 pub struct Environment {
     pub options: EnvironmentOptions,
+    /// Nodes tried in turn to open a connection
+    pub(crate) nodes: Arc<Vec<ClientOptions>>,
+    pub(crate) producer_connections: Arc<ConnectionPool>,
+    pub(crate) consumer_connections: Arc<ConnectionPool>,
 }
 
 impl Environment {
@@ -24,11 +41,22 @@ impl Environment {
         EnvironmentBuilder(EnvironmentOptions::default())
     }
 
-    async fn boostrap(options: EnvironmentOptions) -> RabbitMQStreamResult<Self> {
+    async fn boostrap(mut options: EnvironmentOptions) -> RabbitMQStreamResult<Self> {
+        let nodes = options.nodes()?;
         // check connection
-        let client = Client::connect(options.client_options.clone()).await?;
+        let client = connect_any(&nodes, options.shuffle_uris).await?;
+        options.client_options = client.options().clone();
         client.close().await?;
-        Ok(Environment { options })
+        Ok(Environment {
+            nodes: Arc::new(nodes),
+            producer_connections: Arc::new(ConnectionPool::new(
+                options.max_producers_per_connection,
+            )),
+            consumer_connections: Arc::new(ConnectionPool::new(
+                options.max_consumers_per_connection,
+            )),
+            options,
+        })
     }
 
     /// Returns a builder for creating a stream with a specific configuration
@@ -43,6 +71,10 @@ impl Environment {
             name: None,
             batch_size: 100,
             batch_publishing_delay: Duration::from_millis(100),
+            sub_entry_size: 1,
+            compression: Compression::None,
+            max_in_flight: None,
+            confirm_timeout: None,
             data: PhantomData,
         }
     }
@@ -52,10 +84,104 @@ impl Environment {
         ConsumerBuilder {
             environment: self.clone(),
             offset_specification: OffsetSpecification::Next,
+            name: None,
+            offset_tracking: OffsetTracking::default(),
+            single_active_consumer: false,
+            crc_check: false,
+            end_specification: None,
+            initial_credits: 1,
+            buffer_size: 10000,
+            credit_strategy: Arc::new(ChunkEnqueuedCredit),
+            super_stream: None,
+            consumer_update_listener: None,
+        }
+    }
+    /// Returns a builder for creating a producer on a super stream,
+    /// routing each message to a partition with `routing_strategy`
+    pub fn super_stream_producer(
+        &self,
+        routing_strategy: RoutingStrategy,
+    ) -> SuperStreamProducerBuilder<NoDedup> {
+        SuperStreamProducerBuilder {
+            environment: self.clone(),
+            name: None,
+            batch_size: 100,
+            batch_publishing_delay: Duration::from_millis(100),
+            sub_entry_size: 1,
+            compression: Compression::None,
+            max_in_flight: None,
+            confirm_timeout: None,
+            routing_strategy,
+            data: PhantomData,
         }
     }
+
+    /// Returns a builder for creating a consumer of all the partitions of a super stream
+    pub fn super_stream_consumer(&self) -> SuperStreamConsumerBuilder {
+        SuperStreamConsumerBuilder {
+            environment: self.clone(),
+            offset_specification: OffsetSpecification::Next,
+            name: None,
+            offset_tracking: OffsetTracking::default(),
+            single_active_consumer: false,
+            crc_check: false,
+            end_specification: None,
+            initial_credits: 1,
+            buffer_size: 10000,
+            credit_strategy: Arc::new(ChunkEnqueuedCredit),
+            consumer_update_listener: None,
+        }
+    }
+
+    /// Connect to the first node of the environment which answers
     pub async fn create_client(&self) -> RabbitMQStreamResult<Client> {
-        Client::connect(self.options.client_options.clone()).await
+        connect_any(&self.nodes, self.options.shuffle_uris).await
+    }
+
+    /// Connect to `broker` as advertised by the server, through the load balancer
+    /// or at the address given by the resolver of the environment
+    pub(crate) async fn connect_to_broker(&self, broker: &Broker) -> RabbitMQStreamResult<Client> {
+        if !self.options.load_balancer_mode {
+            let broker = match &self.options.address_resolver {
+                Some(resolver) => resolver.resolve(broker),
+                None => broker.clone(),
+            };
+            return Client::connect(ClientOptions {
+                host: broker.host,
+                port: broker.port as u16,
+                ..self.options.client_options.clone()
+            })
+            .await;
+        }
+
+        // the load balancer picks the node, retry until it is the one we want
+        let policy = &self.options.load_balancer_policy;
+        let mut attempt = 1;
+        loop {
+            let client = self.create_client().await?;
+            let properties = client.connection_properties().await;
+            if is_advertised(&properties, broker) {
+                return Ok(client);
+            }
+            trace!(
+                "Attempt {} reached node {:?}:{:?} instead of {:?}",
+                attempt,
+                properties.get("advertised_host"),
+                properties.get("advertised_port"),
+                broker
+            );
+            client.close().await?;
+
+            if !policy.enabled || policy.exhausted(attempt + 1) {
+                break;
+            }
+            tokio::time::sleep(policy.delay(attempt)).await;
+            attempt += 1;
+        }
+        Err(ClientError::LoadBalancer {
+            host: broker.host.clone(),
+            port: broker.port,
+        })
     }
 
     /// Delete a stream
@@ -71,6 +197,59 @@ impl Environment {
             })
         }
     }
+
+    /// Check if a stream exists
+    pub async fn stream_exists(&self, stream: &str) -> RabbitMQStreamResult<bool> {
+        let client = self.create_client().await?;
+        let exists = client.stream_exists(stream).await;
+        client.close().await?;
+        exists
+    }
+
+    /// Delete a super stream and all its partitions
+    pub async fn delete_super_stream(&self, super_stream: &str) -> Result<(), StreamDeleteError> {
+        let client = self.create_client().await?;
+        let response = client.delete_super_stream(super_stream).await?;
+        client.close().await?;
+
+        if response.is_ok() {
+            Ok(())
+        } else {
+            Err(StreamDeleteError::Delete {
+                stream: super_stream.to_owned(),
+                status: response.code().clone(),
+            })
+        }
+    }
+}
+
+/// Connect to the first of `nodes` which answers, in a random order if `shuffle`
+async fn connect_any(nodes: &[ClientOptions], shuffle: bool) -> RabbitMQStreamResult<Client> {
+    let mut nodes: Vec<&ClientOptions> = nodes.iter().collect();
+    if shuffle {
+        nodes.shuffle(&mut rand::rngs::OsRng);
+    }
+
+    let mut last_error = None;
+    for node in nodes {
+        match Client::connect(node.clone()).await {
+            Ok(client) => return Ok(client),
+            Err(err) => {
+                warn!(
+                    "Failed to connect to {}:{}: {:?}",
+                    node.host, node.port, err
+                );
+                last_error = Some(err);
+            }
+        }
+    }
+    Err(last_error.expect("An environment has at least one node"))
+}
+
+/// Check if the connection `properties` show that it reached `broker`
+fn is_advertised(properties: &HashMap<String, String>, broker: &Broker) -> bool {
+    properties.get("advertised_host") == Some(&broker.host)
+        && properties.get("advertised_port") == Some(&broker.port.to_string())
 }
 
 /// Builder for [`Environment`]
@@ -105,15 +284,177 @@ impl EnvironmentBuilder {
         self
     }
 
+    /// How long to wait for the response of the server to a request
+    pub fn request_timeout(mut self, timeout: Duration) -> EnvironmentBuilder {
+        self.0.client_options.request_timeout = timeout;
+        self
+    }
+
+    /// Connect to the node of `uri`, see [`ClientOptions::with_uri`]
+    pub fn uri(mut self, uri: &str) -> EnvironmentBuilder {
+        self.0.uris = vec![uri.to_owned()];
+        self
+    }
+
+    /// Connect to the first node of `uris` which answers, for the environment
+    /// and for the connections opened later
+    pub fn uris(mut self, uris: &[&str]) -> EnvironmentBuilder {
+        self.0.uris = uris.iter().map(|uri| uri.to_string()).collect();
+        self
+    }
+
+    /// Try the nodes of [`EnvironmentBuilder::uris`] in a random order instead of in turn
+    pub fn shuffle_uris(mut self, shuffle: bool) -> EnvironmentBuilder {
+        self.0.shuffle_uris = shuffle;
+        self
+    }
+
+    /// Map the nodes advertised by the server to reachable addresses
+    pub fn address_resolver(
+        mut self,
+        resolver: impl AddressResolver + 'static,
+    ) -> EnvironmentBuilder {
+        self.0.address_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Always connect through the configured nodes, e.g. a load balancer, until the
+    /// connection reaches the leader or replica of the stream. The address resolver is ignored.
+    pub fn load_balancer_mode(mut self, load_balancer_mode: bool) -> EnvironmentBuilder {
+        self.0.load_balancer_mode = load_balancer_mode;
+        self
+    }
+
+    /// How many times and how often to connect through the load balancer until reaching
+    /// the wanted node, see [`EnvironmentBuilder::load_balancer_mode`].
+    /// Defaults to 10 attempts, starting 100ms apart.
+    pub fn load_balancer_policy(mut self, policy: RecoveryPolicy) -> EnvironmentBuilder {
+        self.0.load_balancer_policy = policy;
+        self
+    }
+
+    /// Connect to the node using TLS, see [`TlsConfiguration`]
+    pub fn tls(mut self, tls_configuration: TlsConfiguration) -> EnvironmentBuilder {
+        self.0.client_options.tls = tls_configuration;
+        self
+    }
+
     pub fn metrics_collector(
         mut self,
-        collector: impl MetricsCollector + Send + Sync + 'static,
+        collector: impl MetricsCollector + 'static,
     ) -> EnvironmentBuilder {
         self.0.client_options.collector = Arc::new(collector);
         self
     }
+
+    /// Listener notified of the events of the connections of the environment,
+    /// e.g. when the server closes one of them
+    pub fn connection_listener(
+        mut self,
+        listener: impl ConnectionListener + 'static,
+    ) -> EnvironmentBuilder {
+        self.0.client_options.connection_listener = Arc::new(listener);
+        self
+    }
+
+    /// How producers and consumers recover from a lost connection, see [`RecoveryPolicy`]
+    pub fn recovery_policy(mut self, policy: RecoveryPolicy) -> EnvironmentBuilder {
+        self.0.recovery_policy = policy;
+        self
+    }
+
+    /// Listener notified of the recovery events of producers and consumers
+    pub fn recovery_listener(
+        mut self,
+        listener: impl RecoveryListener + 'static,
+    ) -> EnvironmentBuilder {
+        self.0.recovery_listener = Arc::new(listener);
+        self
+    }
+
+    /// Producers sharing a connection to the same node, up to 256
+    pub fn max_producers_per_connection(mut self, max: usize) -> EnvironmentBuilder {
+        self.0.max_producers_per_connection = max;
+        self
+    }
+
+    /// Consumers sharing a connection to the same node, up to 256
+    pub fn max_consumers_per_connection(mut self, max: usize) -> EnvironmentBuilder {
+        self.0.max_consumers_per_connection = max;
+        self
+    }
 }
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct EnvironmentOptions {
     pub client_options: ClientOptions,
+    /// URIs of the nodes to connect to, the node of `client_options` if empty
+    pub uris: Vec<String>,
+    pub shuffle_uris: bool,
+    pub address_resolver: Option<Arc<dyn AddressResolver>>,
+    pub load_balancer_mode: bool,
+    pub load_balancer_policy: RecoveryPolicy,
+    pub recovery_policy: RecoveryPolicy,
+    pub recovery_listener: Arc<dyn RecoveryListener>,
+    pub max_producers_per_connection: usize,
+    pub max_consumers_per_connection: usize,
+}
+
+impl Default for EnvironmentOptions {
+    fn default() -> Self {
+        EnvironmentOptions {
+            client_options: ClientOptions::default(),
+            uris: vec![],
+            shuffle_uris: false,
+            address_resolver: None,
+            load_balancer_mode: false,
+            load_balancer_policy: RecoveryPolicy::default()
+                .initial_delay(Duration::from_millis(100))
+                .max_delay(Duration::from_secs(2))
+                .max_attempts(10),
+            recovery_policy: RecoveryPolicy::default(),
+            recovery_listener: Arc::new(NopRecoveryListener {}),
+            max_producers_per_connection: 256,
+            max_consumers_per_connection: 256,
+        }
+    }
+}
+
+impl EnvironmentOptions {
+    /// Nodes to connect to, the ones of `uris` or the one of `client_options`
+    fn nodes(&self) -> RabbitMQStreamResult<Vec<ClientOptions>> {
+        if self.uris.is_empty() {
+            return Ok(vec![self.client_options.clone()]);
+        }
+        self.uris
+            .iter()
+            .map(|uri| self.client_options.with_uri(uri))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::is_advertised;
+    use crate::client::Broker;
+
+    #[test]
+    fn is_advertised_test() {
+        let broker = Broker {
+            host: "node-1".to_owned(),
+            port: 5552,
+        };
+        let properties = |host: &str, port: &str| {
+            HashMap::from([
+                ("advertised_host".to_owned(), host.to_owned()),
+                ("advertised_port".to_owned(), port.to_owned()),
+            ])
+        };
+
+        assert!(is_advertised(&properties("node-1", "5552"), &broker));
+        assert!(!is_advertised(&properties("node-2", "5552"), &broker));
+        assert!(!is_advertised(&properties("node-1", "5553"), &broker));
+        assert!(!is_advertised(&HashMap::new(), &broker));
+    }
 }
@@ -17,6 +17,64 @@ pub enum ClientError {
     GenericError(#[from] Box<dyn std::error::Error + Send + Sync>),
     #[error("Client already closed")]
     AlreadyClosed,
+    #[error("No heartbeat received from the server")]
+    HeartbeatTimeout,
+    #[error("No response received from the server in time")]
+    Timeout,
+    #[error("Connection closed before the response")]
+    ConnectionClosed,
+    #[error("Connection closed by the server with status {code:?}: {reason}")]
+    ConnectionClosedByServer { code: ResponseCode, reason: String },
+    #[error("Request failed with status {0:?}")]
+    RequestError(ResponseCode),
+    #[error("Message of {size} bytes does not fit in a frame of {max_frame_size} bytes")]
+    MessageTooLarge { size: u32, max_frame_size: u32 },
+    #[error(transparent)]
+    Tls(#[from] rustls::Error),
+    #[error("Invalid connection URI: {0}")]
+    InvalidUri(String),
+    #[error("No connection through the load balancer reached node {host}:{port}")]
+    LoadBalancer { host: String, port: u32 },
+}
+
+impl ClientError {
+    /// Copy of the error for each of the handlers it is sent to, the errors
+    /// which cannot be cloned are kept with their kind or their description
+    pub(crate) fn duplicate(&self) -> ClientError {
+        match self {
+            ClientError::Io(err) => {
+                ClientError::Io(std::io::Error::new(err.kind(), err.to_string()))
+            }
+            ClientError::CastError(message) => ClientError::CastError(message.clone()),
+            ClientError::Protocol(_) | ClientError::GenericError(_) => {
+                ClientError::GenericError(self.to_string().into())
+            }
+            ClientError::AlreadyClosed => ClientError::AlreadyClosed,
+            ClientError::HeartbeatTimeout => ClientError::HeartbeatTimeout,
+            ClientError::Timeout => ClientError::Timeout,
+            ClientError::ConnectionClosed => ClientError::ConnectionClosed,
+            ClientError::ConnectionClosedByServer { code, reason } => {
+                ClientError::ConnectionClosedByServer {
+                    code: code.clone(),
+                    reason: reason.clone(),
+                }
+            }
+            ClientError::RequestError(code) => ClientError::RequestError(code.clone()),
+            ClientError::MessageTooLarge {
+                size,
+                max_frame_size,
+            } => ClientError::MessageTooLarge {
+                size: *size,
+                max_frame_size: *max_frame_size,
+            },
+            ClientError::Tls(err) => ClientError::Tls(err.clone()),
+            ClientError::InvalidUri(uri) => ClientError::InvalidUri(uri.clone()),
+            ClientError::LoadBalancer { host, port } => ClientError::LoadBalancer {
+                host: host.clone(),
+                port: *port,
+            },
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -88,8 +146,23 @@ pub enum ProducerPublishError {
     Batch { stream: String },
     #[error("Failed to publish message, the producer is closed")]
     Closed,
+    #[error("Failed to publish message, {max_in_flight} messages are waiting for confirmation")]
+    MaxInFlight { max_in_flight: usize },
+    #[error("Confirmation of message {publishing_id} timed out")]
+    Timeout { publishing_id: u64 },
+    #[error("Publishing id {publishing_id} is not greater than the last one {last_publishing_id}")]
+    PublishingIdNotIncreasing {
+        publishing_id: u64,
+        last_publishing_id: u64,
+    },
+    #[error("Connection of the producer of stream {stream} lost before the confirmation")]
+    ConnectionLost { stream: String },
+    #[error("Stream {stream} moved or was deleted before the confirmation")]
+    StreamUnavailable { stream: String },
     #[error("Failed to publish message, confirmation channel returned None for stream {stream}")]
     Confirmation { stream: String },
+    #[error("Failed to publish message, no partition of super stream {super_stream} matches it")]
+    Unroutable { super_stream: String },
     #[error(transparent)]
     Client(#[from] ClientError),
 }
@@ -114,6 +187,9 @@ pub enum ConsumerCreateError {
         status: ResponseCode,
     },
 
+    #[error("Single active consumer for stream {stream} requires a consumer name")]
+    SingleActiveConsumerWithoutName { stream: String },
+
     #[error("Stream {stream} does not exist")]
     StreamDoesNotExist { stream: String },
 
@@ -128,9 +204,21 @@ pub enum ConsumerDeliveryError {
         stream: String,
         status: ResponseCode,
     },
+    #[error("CRC of chunk {chunk_id} does not match its data")]
+    Crc { chunk_id: u64 },
+    #[error("Stream {stream} was deleted")]
+    StreamDeleted { stream: String },
     #[error(transparent)]
     Client(#[from] ClientError),
 }
+#[derive(Error, Debug)]
+pub enum ConsumerStoreOffsetError {
+    #[error("Storing the offset of a consumer of stream {stream} requires a consumer name")]
+    NameMissing { stream: String },
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
 #[derive(Error, Debug)]
 pub enum ConsumerCloseError {
     #[error("Failed to close consumer for stream {stream} status {status:?}")]
@@ -143,3 +231,33 @@ pub enum ConsumerCloseError {
     #[error(transparent)]
     Client(#[from] ClientError),
 }
+
+#[cfg(test)]
+mod tests {
+    use rabbitmq_stream_protocol::ResponseCode;
+
+    use super::ClientError;
+
+    #[test]
+    fn duplicate_keeps_the_variant_test() {
+        let closed = ClientError::ConnectionClosedByServer {
+            code: ResponseCode::Ok,
+            reason: "forced".to_owned(),
+        };
+        assert!(matches!(
+            closed.duplicate(),
+            ClientError::ConnectionClosedByServer { code: ResponseCode::Ok, reason }
+                if reason == "forced"
+        ));
+        assert!(matches!(
+            ClientError::HeartbeatTimeout.duplicate(),
+            ClientError::HeartbeatTimeout
+        ));
+
+        let io = ClientError::Io(std::io::ErrorKind::ConnectionReset.into());
+        assert!(matches!(
+            io.duplicate(),
+            ClientError::Io(err) if err.kind() == std::io::ErrorKind::ConnectionReset
+        ));
+    }
+}
@@ -73,27 +73,51 @@
 
 mod byte_capacity;
 mod client;
+mod connection_pool;
 mod consumer;
+mod credit;
 mod environment;
 pub mod error;
 mod offset_specification;
 mod producer;
+mod recovery;
+mod resolver;
 mod stream_creator;
+mod superstream;
+mod superstream_consumer;
+mod superstream_producer;
 
 pub type RabbitMQStreamResult<T> = Result<T, error::ClientError>;
 
-pub use crate::client::{Client, ClientOptions, MetricsCollector};
+pub use crate::client::{
+    Client, ClientOptions, ConnectionEvent, ConnectionListener, MetricsCollector, TlsConfiguration,
+};
 
-pub use crate::consumer::{Consumer, ConsumerBuilder, ConsumerHandle};
+pub use crate::consumer::{
+    Consumer, ConsumerBuilder, ConsumerChunks, ConsumerHandle, ConsumerUpdateContext,
+    OffsetTracking,
+};
+pub use crate::credit::{
+    ByteBudgetCredit, ChunkConsumedCredit, ChunkEnqueuedCredit, CreditStrategy,
+};
 pub use crate::environment::{Environment, EnvironmentBuilder};
 pub use crate::producer::{Dedup, NoDedup, Producer, ProducerBuilder};
+pub use crate::recovery::{RecoveryEvent, RecoveryListener, RecoveryPolicy};
+pub use crate::resolver::AddressResolver;
+pub use crate::superstream::RoutingStrategy;
+pub use crate::superstream_consumer::{
+    SuperStreamConsumer, SuperStreamConsumerBuilder, SuperStreamConsumerHandle,
+};
+pub use crate::superstream_producer::{SuperStreamProducer, SuperStreamProducerBuilder};
 pub mod types {
 
     pub use crate::byte_capacity::ByteCapacity;
     pub use crate::client::{Broker, MessageResult, StreamMetadata};
-    pub use crate::consumer::Delivery;
+    pub use crate::consumer::{Delivery, EndSpecification};
     pub use crate::offset_specification::OffsetSpecification;
+    pub use crate::producer::ProducerCloseSummary;
     pub use crate::stream_creator::StreamCreator;
+    pub use rabbitmq_stream_protocol::compression::Compression;
     pub use rabbitmq_stream_protocol::message::Message;
     pub use rabbitmq_stream_protocol::{Response, ResponseCode, ResponseKind};
 }
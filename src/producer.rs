@@ -1,30 +1,36 @@
 use dashmap::DashMap;
 use futures::{future::BoxFuture, FutureExt};
-use rabbitmq_stream_protocol::{message::Message, ResponseCode, ResponseKind};
+use rabbitmq_stream_protocol::{
+    compression::Compression, message::Message, ResponseCode, ResponseKind,
+};
 use std::future::Future;
 use std::vec;
 use std::{
     marker::PhantomData,
     sync::{
-        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Weak,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::sync::mpsc::channel;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, Mutex, Notify, RwLock, Semaphore};
 use tracing::{debug, error, trace};
 
 use crate::MetricsCollector;
-use crate::{client::MessageHandler, ClientOptions, RabbitMQStreamResult};
+use crate::{client::MessageHandler, RabbitMQStreamResult};
 use crate::{
     client::{Client, MessageResult},
     environment::Environment,
     error::{ClientError, ProducerCloseError, ProducerCreateError, ProducerPublishError},
+    recovery::{self, Migration},
 };
 
 type WaiterMap = Arc<DashMap<u64, ProducerMessageWaiter>>;
 
+/// Ids of the messages packed in a sub-entry, except the last one which is the id of the sub-entry
+type SubEntryMap = Arc<DashMap<u64, Vec<u64>>>;
+
 type ConfirmCallback = Arc<
     dyn Fn(Result<ConfirmationStatus, ProducerPublishError>) -> BoxFuture<'static, ()>
         + Send
@@ -35,9 +41,35 @@ type ConfirmCallback = Arc<
 pub struct ConfirmationStatus {
     publishing_id: u64,
     confirmed: bool,
+    duplicate: bool,
     status: ResponseCode,
 }
 
+/// Outcome of the messages of a producer closed with [`Producer::close_drain`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProducerCloseSummary {
+    confirmed: u64,
+    failed: u64,
+    abandoned: u64,
+}
+
+impl ProducerCloseSummary {
+    /// Messages confirmed by the broker while draining
+    pub fn confirmed(&self) -> u64 {
+        self.confirmed
+    }
+
+    /// Messages rejected by the broker or failed while draining
+    pub fn failed(&self) -> u64 {
+        self.failed
+    }
+
+    /// Messages still waiting for confirmation when the deadline expired
+    pub fn abandoned(&self) -> u64 {
+        self.abandoned
+    }
+}
+
 impl ConfirmationStatus {
     /// Get a reference to the confirmation status's confirmed.
     pub fn confirmed(&self) -> bool {
@@ -49,6 +81,16 @@ impl ConfirmationStatus {
         self.publishing_id
     }
 
+    /// Whether the publishing id is not greater than the last one the broker had stored
+    /// for the name of the producer when the producer connected or reconnected.
+    ///
+    /// The broker confirms such messages without storing them again. This is inferred by
+    /// the client from the stored sequence, the broker does not tell, and a stored sequence
+    /// of 0 is taken as nothing stored.
+    pub fn is_duplicate(&self) -> bool {
+        self.duplicate
+    }
+
     /// Get a reference to the confirmation status's status.
     pub fn status(&self) -> &ResponseCode {
         &self.status
@@ -56,26 +98,418 @@ impl ConfirmationStatus {
 }
 
 pub struct ProducerInternal {
-    client: Client,
+    environment: Environment,
+    client: RwLock<Client>,
     stream: String,
-    producer_id: u8,
+    name: Option<String>,
+    producer_id: AtomicU8,
     batch_size: usize,
+    sub_entry_size: usize,
+    compression: Compression,
     publish_sequence: Arc<AtomicU64>,
+    /// Last publishing id assigned to a message of a named producer
+    last_publishing_id: std::sync::Mutex<Option<u64>>,
+    /// Last publishing id stored by the broker when the named producer connected
+    stored_sequence: Arc<AtomicU64>,
     waiting_confirmations: WaiterMap,
+    /// Notified when no message is waiting for confirmation anymore
+    all_confirmed: Arc<Notify>,
+    sub_entries: SubEntryMap,
+    max_in_flight: Option<usize>,
+    in_flight: Option<Arc<Semaphore>>,
     closed: Arc<AtomicBool>,
     accumulator: MessageAccumulator,
+    /// Held from the assignment of the publishing ids until the messages are sent,
+    /// so that the broker receives them in increasing order
+    publish_lock: Mutex<()>,
+    confirmed_count: Arc<AtomicU64>,
+    failed_count: Arc<AtomicU64>,
 }
 
 impl ProducerInternal {
-    async fn batch_send(&self) -> Result<(), ProducerPublishError> {
+    async fn client(&self) -> Client {
+        self.client.read().await.clone()
+    }
+
+    fn producer_id(&self) -> u8 {
+        self.producer_id.load(Ordering::SeqCst)
+    }
+
+    /// Wait until `count` more messages can be waiting for confirmation
+    async fn acquire_in_flight(&self, count: usize) -> Result<(), ProducerPublishError> {
+        if let Some(in_flight) = &self.in_flight {
+            in_flight
+                .acquire_many(count as u32)
+                .await
+                .map_err(|_| ProducerPublishError::Closed)?
+                .forget();
+        }
+        Ok(())
+    }
+
+    fn try_acquire_in_flight(&self, count: usize) -> Result<(), ProducerPublishError> {
+        if let (Some(in_flight), Some(max_in_flight)) = (&self.in_flight, self.max_in_flight) {
+            in_flight
+                .try_acquire_many(count as u32)
+                .map_err(|_| ProducerPublishError::MaxInFlight { max_in_flight })?
+                .forget();
+        }
+        Ok(())
+    }
+
+    /// Give back `count` messages waiting for confirmation
+    fn release_in_flight(&self, count: usize) {
+        if let Some(in_flight) = &self.in_flight {
+            in_flight.add_permits(count);
+        }
+    }
+
+    /// Set the publishing id of `message`, the next of the sequence if not set by the user.
+    ///
+    /// The publishing ids of a named producer must be strictly increasing for the
+    /// deduplication of the broker to work.
+    fn assign_publishing_id(&self, message: &mut Message) -> Result<u64, ProducerPublishError> {
+        let publishing_id = match (message.publishing_id().copied(), &self.name) {
+            (None, None) => self.publish_sequence.fetch_add(1, Ordering::Relaxed),
+            (Some(publishing_id), None) => publishing_id,
+            (publishing_id, Some(_)) => {
+                let mut last = self.last_publishing_id.lock().unwrap();
+                let publishing_id = match publishing_id {
+                    Some(publishing_id) => {
+                        if let Some(last_publishing_id) = *last {
+                            if publishing_id <= last_publishing_id {
+                                return Err(ProducerPublishError::PublishingIdNotIncreasing {
+                                    publishing_id,
+                                    last_publishing_id,
+                                });
+                            }
+                        }
+                        self.publish_sequence
+                            .fetch_max(publishing_id + 1, Ordering::Relaxed);
+                        publishing_id
+                    }
+                    None => self.publish_sequence.fetch_add(1, Ordering::Relaxed),
+                };
+                *last = Some(publishing_id);
+                publishing_id
+            }
+        };
+        message.set_publishing_id(publishing_id);
+        Ok(publishing_id)
+    }
+
+    /// Fail the messages waiting for confirmation for longer than `timeout`
+    async fn expire_waiters(&self, timeout: Duration) {
+        let expired: Vec<u64> = self
+            .waiting_confirmations
+            .iter()
+            .filter(|waiter| waiter.created.elapsed() >= timeout)
+            .map(|waiter| *waiter.key())
+            .collect();
+
+        let count = self
+            .fail_waiters(expired, |publishing_id| ProducerPublishError::Timeout {
+                publishing_id,
+            })
+            .await;
+        if count > 0 {
+            debug!("Confirmation of {} messages timed out", count);
+        }
+    }
+
+    /// Fail every message waiting for confirmation, dropping the ones not sent yet
+    async fn fail_all_waiters(&self, error: impl Fn(u64) -> ProducerPublishError) {
+        let mut dropped = 0;
+        while let Ok(Some((message, waiter))) = self.accumulator.get().await {
+            dropped += 1;
+            self.release_in_flight(1);
+            let publishing_id = message.publishing_id().copied().unwrap_or_default();
+            waiter.handle_failure(error(publishing_id)).await;
+        }
+        self.failed_count.fetch_add(dropped, Ordering::Relaxed);
+
+        let publishing_ids: Vec<u64> = self
+            .waiting_confirmations
+            .iter()
+            .map(|waiter| *waiter.key())
+            .collect();
+
+        let count = self.fail_waiters(publishing_ids, error).await;
+        if count > 0 {
+            debug!("Failed {} messages waiting for confirmation", count);
+        }
+        self.sub_entries.clear();
+    }
+
+    /// Complete the waiters of `publishing_ids` with an error, returns how many were waiting
+    async fn fail_waiters(
+        &self,
+        publishing_ids: Vec<u64>,
+        error: impl Fn(u64) -> ProducerPublishError,
+    ) -> u64 {
+        let mut count = 0;
+        for publishing_id in publishing_ids {
+            if let Some((_, waiter)) = self.waiting_confirmations.remove(&publishing_id) {
+                count += 1;
+                self.sub_entries.remove(&publishing_id);
+                self.release_in_flight(1);
+                waiter.handle_failure(error(publishing_id)).await;
+            }
+        }
+        if self.waiting_confirmations.is_empty() {
+            self.all_confirmed.notify_waiters();
+        }
+
+        if count > 0 {
+            self.failed_count.fetch_add(count, Ordering::Relaxed);
+            self.environment
+                .options
+                .client_options
+                .collector
+                .publish_error(count)
+                .await;
+        }
+        count
+    }
+
+    /// Fail the waiters of `publishing_ids` whose messages could not be published.
+    ///
+    /// The callbacks run in their own task, the caller holds the publish lock.
+    async fn fail_unpublished(&self, publishing_ids: &[u64], error: &ClientError) {
+        let mut count = 0;
+        for publishing_id in publishing_ids {
+            if let Some((_, waiter)) = self.waiting_confirmations.remove(publishing_id) {
+                count += 1;
+                self.sub_entries.remove(publishing_id);
+                self.release_in_flight(1);
+                tokio::task::spawn(waiter.handle_failure(error.duplicate().into()));
+            }
+        }
+        if self.waiting_confirmations.is_empty() {
+            self.all_confirmed.notify_waiters();
+        }
+
+        if count > 0 {
+            self.failed_count.fetch_add(count, Ordering::Relaxed);
+            self.environment
+                .options
+                .client_options
+                .collector
+                .publish_error(count)
+                .await;
+        }
+    }
+
+    /// Publish all the messages of the accumulator, returns false if one of the batches failed
+    async fn flush(&self) -> bool {
+        loop {
+            match self.batch_send().await {
+                Ok(0) => return true,
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Error publishing batch {:?}", e);
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Wait until no message is waiting for confirmation, returns false if `timeout` expired first
+    async fn wait_confirmations(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                // registered before checking, so that a notification in between is not missed
+                let notified = self.all_confirmed.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if self.waiting_confirmations.is_empty() {
+                    break;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    /// Delete the publisher, fail the messages still waiting and give back the connection
+    async fn shutdown(&self) -> Result<(), ProducerCloseError> {
+        // the batches being published add their waiters before they are failed,
+        // the ones published afterwards see the producer closed
+        let publishing = self.publish_lock.lock().await;
+        self.accumulator.close().await;
+        drop(publishing);
+        if let Some(in_flight) = &self.in_flight {
+            in_flight.close();
+        }
+
+        let client = self.client().await;
+        let producer_id = self.producer_id();
+        let response = client.delete_publisher(producer_id).await;
+        self.fail_all_waiters(|_| ProducerPublishError::Closed)
+            .await;
+        self.release(&client, producer_id).await;
+
+        let response = response?;
+        if response.is_ok() {
+            Ok(())
+        } else {
+            Err(ProducerCloseError::Close {
+                status: response.code().clone(),
+                stream: self.stream.clone(),
+            })
+        }
+    }
+
+    fn connection_lost(&self) -> ProducerPublishError {
+        ProducerPublishError::ConnectionLost {
+            stream: self.stream.clone(),
+        }
+    }
+
+    /// Give back the publisher id and connection to the pool of the environment
+    async fn release(&self, client: &Client, producer_id: u8) {
+        client.remove_publisher_handler(producer_id).await;
+        self.environment
+            .producer_connections
+            .release(client, producer_id)
+            .await;
+    }
+
+    /// Register the confirm handler on `client` and declare the publisher
+    async fn attach(self: &Arc<Self>, client: &Client) -> Result<(), ProducerCreateError> {
+        let confirm_handler = ProducerConfirmHandler {
+            waiting_confirmations: self.waiting_confirmations.clone(),
+            all_confirmed: self.all_confirmed.clone(),
+            sub_entries: self.sub_entries.clone(),
+            in_flight: self.in_flight.clone(),
+            stored_sequence: self.name.as_ref().map(|_| self.stored_sequence.clone()),
+            confirmed_count: self.confirmed_count.clone(),
+            failed_count: self.failed_count.clone(),
+            metrics_collector: self.environment.options.client_options.collector.clone(),
+            producer: Arc::downgrade(self),
+        };
+
+        client
+            .set_publisher_handler(self.producer_id(), confirm_handler)
+            .await;
+
+        let response = client
+            .declare_publisher(self.producer_id(), self.name.clone(), &self.stream)
+            .await?;
+
+        if response.is_ok() {
+            Ok(())
+        } else {
+            Err(ProducerCreateError::Create {
+                stream: self.stream.clone(),
+                status: response.code().clone(),
+            })
+        }
+    }
+
+    async fn recover(self: Arc<Self>) {
+        self.release(&self.client().await, self.producer_id()).await;
+
+        let options = &self.environment.options;
+        let recovered = recovery::recover(
+            &options.recovery_policy,
+            options.recovery_listener.as_ref(),
+            &self.stream,
+            &self.closed,
+            || self.reconnect(),
+        )
+        .await;
+
+        if !recovered {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Reattach to the new leader of the stream after the server removed the publisher
+    async fn migrate(self: Arc<Self>) {
+        // the publisher does not exist anymore, there is nothing to delete
+        self.release(&self.client().await, self.producer_id()).await;
+        self.fail_all_waiters(|_| ProducerPublishError::StreamUnavailable {
+            stream: self.stream.clone(),
+        })
+        .await;
+
+        let migration = recovery::migrate(&self.environment, &self.stream, &self.closed, || {
+            self.reconnect()
+        })
+        .await;
+        if migration != Migration::Recovered {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    async fn reconnect(self: &Arc<Self>) -> Result<(), ProducerCreateError> {
+        let (client, producer_id) = connect_to_leader(&self.environment, &self.stream).await?;
+        self.producer_id.store(producer_id, Ordering::SeqCst);
+
+        if let Err(err) = self.resume(&client).await {
+            self.release(&client, producer_id).await;
+            return Err(err);
+        }
+
+        // checked under the lock, close uses either the old client or this one
+        let mut current = self.client.write().await;
+        if self.closed.load(Ordering::SeqCst) {
+            drop(current);
+            trace!("Producer closed while reconnecting, deleting the publisher");
+            let _ = client.delete_publisher(producer_id).await;
+            self.release(&client, producer_id).await;
+        } else {
+            *current = client;
+        }
+        Ok(())
+    }
+
+    async fn resume(self: &Arc<Self>, client: &Client) -> Result<(), ProducerCreateError> {
+        self.attach(client).await?;
+
+        if let Some(name) = &self.name {
+            // resume after the last message stored by the broker, never going back
+            // on ids already assigned
+            let sequence = client.query_publisher_sequence(name, &self.stream).await?;
+            self.stored_sequence.store(sequence, Ordering::Relaxed);
+            self.publish_sequence
+                .fetch_max(sequence + 1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Publish the next batch of the accumulator, returns the number of messages taken from it
+    async fn batch_send(&self) -> Result<usize, ProducerPublishError> {
+        let _publishing = self.publish_lock.lock().await;
+        self.send_accumulated().await
+    }
+
+    /// Assign the publishing ids of the next batch of the accumulator and publish it,
+    /// the caller holds the publish lock
+    async fn send_accumulated(&self) -> Result<usize, ProducerPublishError> {
         let mut count = 0;
         let mut messages = Vec::with_capacity(self.batch_size);
+        let mut publishing_ids = Vec::with_capacity(self.batch_size);
 
         while count != self.batch_size {
             match self.accumulator.get().await? {
-                Some(message) => {
-                    messages.push(message);
+                Some((mut message, waiter)) => {
                     count += 1;
+                    match self.assign_publishing_id(&mut message) {
+                        Ok(publishing_id) => {
+                            self.waiting_confirmations.insert(publishing_id, waiter);
+                            publishing_ids.push(publishing_id);
+                            messages.push(message);
+                        }
+                        Err(err) => {
+                            self.release_in_flight(1);
+                            self.failed_count.fetch_add(1, Ordering::Relaxed);
+                            // not called under the publish lock, the callback may publish
+                            tokio::task::spawn(waiter.handle_failure(err));
+                        }
+                    }
                 }
                 _ => break,
             }
@@ -83,22 +517,89 @@ impl ProducerInternal {
 
         if !messages.is_empty() {
             debug!("Sending batch of {} messages", messages.len());
-            self.client.publish(self.producer_id, messages).await?;
+            if let Err(err) = self.publish(messages).await {
+                self.fail_unpublished(&publishing_ids, &err).await;
+                return Err(err.into());
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Publish `messages`, packing them in sub-entries if enabled
+    async fn publish(&self, messages: Vec<Message>) -> Result<(), ClientError> {
+        let client = self.client().await;
+        if self.sub_entry_size <= 1 {
+            client.publish(self.producer_id(), messages).await?;
+            return Ok(());
+        }
+
+        let mut entries = Vec::with_capacity(messages.len() / self.sub_entry_size + 1);
+        let mut messages = messages.into_iter();
+        loop {
+            let entry: Vec<Message> = messages.by_ref().take(self.sub_entry_size).collect();
+            if entry.is_empty() {
+                break;
+            }
+            // the broker confirms only the id of the last message of the sub-entry
+            let ids: Vec<u64> = entry
+                .iter()
+                .filter_map(|message| message.publishing_id().copied())
+                .collect();
+            if let Some((last, others)) = ids.split_last() {
+                if !others.is_empty() {
+                    self.sub_entries.insert(*last, others.to_vec());
+                }
+            }
+            entries.push(entry);
         }
 
+        client
+            .publish_sub_entries(self.producer_id(), entries, self.compression)
+            .await?;
         Ok(())
     }
 }
 /// API for publising messages to RabbitMQ stream
-#[derive(Clone)]
 pub struct Producer<T>(Arc<ProducerInternal>, PhantomData<T>);
 
+impl<T> Clone for Producer<T> {
+    fn clone(&self) -> Self {
+        Producer(self.0.clone(), PhantomData)
+    }
+}
+
+impl Producer<Dedup> {
+    /// Last publishing id assigned to a message sent by this producer, `None` until
+    /// the first message is sent
+    pub fn last_publishing_id(&self) -> Option<u64> {
+        *self.0.last_publishing_id.lock().unwrap()
+    }
+
+    /// Ask the broker for the last publishing id it stored for the name of the producer,
+    /// 0 if none was stored
+    pub async fn query_last_publishing_id(&self) -> Result<u64, ProducerPublishError> {
+        let name = self.0.name.as_deref().unwrap_or_default();
+        let sequence = self
+            .0
+            .client()
+            .await
+            .query_publisher_sequence(name, &self.0.stream)
+            .await?;
+        Ok(sequence)
+    }
+}
+
 /// Builder for [`Producer`]
 pub struct ProducerBuilder<T> {
     pub environment: Environment,
     pub name: Option<String>,
     pub batch_size: usize,
     pub batch_publishing_delay: Duration,
+    pub sub_entry_size: usize,
+    pub compression: Compression,
+    pub max_in_flight: Option<usize>,
+    pub confirm_timeout: Option<Duration>,
     pub data: PhantomData<T>,
 }
 
@@ -107,73 +608,60 @@ pub struct NoDedup {}
 pub struct Dedup {}
 impl<T> ProducerBuilder<T> {
     pub async fn build(self, stream: &str) -> Result<Producer<T>, ProducerCreateError> {
-        // Connect to the user specified node first, then look for the stream leader.
-        // The leader is the recommended node for writing, because writing to a replica will redundantly pass these messages
-        // to the leader anyway - it is the only one capable of writing.
-        let mut client = self.environment.create_client().await?;
-        let metrics_collector = self.environment.options.client_options.collector.clone();
-        if let Some(metadata) = client.metadata(vec![stream.to_string()]).await?.get(stream) {
-            tracing::debug!(
-                "Connecting to leader node {:?} of stream {}",
-                metadata.leader,
-                stream
-            );
-            client = Client::connect(ClientOptions {
-                host: metadata.leader.host.clone(),
-                port: metadata.leader.port as u16,
-                ..self.environment.options.client_options
-            })
-            .await?;
-        } else {
-            return Err(ProducerCreateError::StreamDoesNotExist {
-                stream: stream.into(),
-            });
-        }
-
-        let waiting_confirmations: WaiterMap = Arc::new(DashMap::new());
+        let (client, producer_id) = connect_to_leader(&self.environment, stream).await?;
 
-        let confirm_handler = ProducerConfirmHandler {
-            waiting_confirmations: waiting_confirmations.clone(),
-            metrics_collector,
-        };
-
-        client.set_handler(confirm_handler).await;
-
-        let producer_id = 1;
-        let response = client
-            .declare_publisher(producer_id, self.name.clone(), stream)
-            .await?;
-
-        let publish_sequence = if let Some(name) = self.name {
-            let sequence = client.query_publisher_sequence(&name, stream).await?;
-            Arc::new(AtomicU64::new(sequence))
-        } else {
-            Arc::new(AtomicU64::new(0))
+        // a named producer starts after the last message stored by the broker
+        let (stored_sequence, publish_sequence) = match &self.name {
+            Some(name) => match client.query_publisher_sequence(name, stream).await {
+                Ok(sequence) => (sequence, sequence + 1),
+                Err(err) => {
+                    self.environment
+                        .producer_connections
+                        .release(&client, producer_id)
+                        .await;
+                    return Err(err.into());
+                }
+            },
+            None => (0, 0),
         };
 
-        if response.is_ok() {
-            let producer = ProducerInternal {
-                producer_id,
-                batch_size: self.batch_size,
-                stream: stream.to_string(),
-                client,
-                publish_sequence,
-                waiting_confirmations,
-                closed: Arc::new(AtomicBool::new(false)),
-                accumulator: MessageAccumulator::new(self.batch_size),
-            };
+        let producer = Arc::new(ProducerInternal {
+            environment: self.environment,
+            client: RwLock::new(client.clone()),
+            producer_id: AtomicU8::new(producer_id),
+            batch_size: self.batch_size,
+            sub_entry_size: self.sub_entry_size,
+            compression: self.compression,
+            stream: stream.to_string(),
+            name: self.name,
+            publish_sequence: Arc::new(AtomicU64::new(publish_sequence)),
+            last_publishing_id: std::sync::Mutex::new(None),
+            stored_sequence: Arc::new(AtomicU64::new(stored_sequence)),
+            waiting_confirmations: Arc::new(DashMap::new()),
+            all_confirmed: Arc::new(Notify::new()),
+            sub_entries: Arc::new(DashMap::new()),
+            max_in_flight: self.max_in_flight,
+            in_flight: self
+                .max_in_flight
+                .map(|max_in_flight| Arc::new(Semaphore::new(max_in_flight))),
+            closed: Arc::new(AtomicBool::new(false)),
+            accumulator: MessageAccumulator::new(self.batch_size),
+            publish_lock: Mutex::new(()),
+            confirmed_count: Arc::new(AtomicU64::new(0)),
+            failed_count: Arc::new(AtomicU64::new(0)),
+        });
 
-            let internal_producer = Arc::new(producer);
-            let producer = Producer(internal_producer.clone(), PhantomData);
-            schedule_batch_send(internal_producer, self.batch_publishing_delay);
+        if let Err(err) = producer.attach(&client).await {
+            producer.release(&client, producer_id).await;
+            return Err(err);
+        }
 
-            Ok(producer)
-        } else {
-            Err(ProducerCreateError::Create {
-                stream: stream.to_owned(),
-                status: response.code().clone(),
-            })
+        schedule_batch_send(producer.clone(), self.batch_publishing_delay);
+        if let Some(timeout) = self.confirm_timeout {
+            schedule_confirm_timeout(Arc::downgrade(&producer), timeout);
         }
+
+        Ok(Producer(producer, PhantomData))
     }
 
     pub fn batch_size(mut self, batch_size: usize) -> Self {
@@ -185,6 +673,33 @@ impl<T> ProducerBuilder<T> {
         self.batch_publishing_delay = delay;
         self
     }
+
+    /// Pack up to `sub_entry_size` messages of a batch in a single entry,
+    /// a value of 1 publishes each message in its own entry
+    pub fn sub_entry_size(mut self, sub_entry_size: usize) -> Self {
+        self.sub_entry_size = sub_entry_size.clamp(1, u16::MAX as usize);
+        self
+    }
+
+    /// Compression of the sub-entries, used only when `sub_entry_size` is greater than 1
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Make `send` and `batch_send` wait once `max_in_flight` messages are waiting for
+    /// confirmation, see [`Producer::try_send`] for failing instead
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.clamp(1, Semaphore::MAX_PERMITS));
+        self
+    }
+
+    /// Fail with [`ProducerPublishError::Timeout`] the messages not confirmed within `timeout`
+    pub fn confirm_timeout(mut self, timeout: Duration) -> Self {
+        self.confirm_timeout = Some(timeout);
+        self
+    }
+
     pub fn name(mut self, name: &str) -> ProducerBuilder<Dedup> {
         self.name = Some(name.to_owned());
         ProducerBuilder {
@@ -192,14 +707,51 @@ impl<T> ProducerBuilder<T> {
             name: self.name,
             batch_size: self.batch_size,
             batch_publishing_delay: self.batch_publishing_delay,
+            sub_entry_size: self.sub_entry_size,
+            compression: self.compression,
+            max_in_flight: self.max_in_flight,
+            confirm_timeout: self.confirm_timeout,
             data: PhantomData,
         }
     }
 }
 
+/// Connect to the user specified node first, then look for the stream leader.
+/// The leader is the recommended node for writing, because writing to a replica will redundantly pass these messages
+/// to the leader anyway - it is the only one capable of writing.
+///
+/// The connection to the leader comes from the pool of the environment, along with a free publisher id.
+async fn connect_to_leader(
+    environment: &Environment,
+    stream: &str,
+) -> Result<(Client, u8), ProducerCreateError> {
+    let client = environment.create_client().await?;
+    match client.metadata(vec![stream.to_string()]).await?.get(stream) {
+        Some(metadata) => {
+            tracing::debug!(
+                "Connecting to leader node {:?} of stream {}",
+                metadata.leader,
+                stream
+            );
+            let leader = environment
+                .producer_connections
+                .acquire(&metadata.leader, || {
+                    environment.connect_to_broker(&metadata.leader)
+                })
+                .await?;
+            client.close().await?;
+            Ok(leader)
+        }
+        None => Err(ProducerCreateError::StreamDoesNotExist {
+            stream: stream.into(),
+        }),
+    }
+}
+
+/// Messages waiting for the next batch, their publishing ids are assigned when they are sent
 pub struct MessageAccumulator {
-    sender: mpsc::Sender<Message>,
-    receiver: Mutex<mpsc::Receiver<Message>>,
+    sender: mpsc::Sender<(Message, ProducerMessageWaiter)>,
+    receiver: Mutex<mpsc::Receiver<(Message, ProducerMessageWaiter)>>,
     capacity: usize,
     message_count: AtomicUsize,
 }
@@ -215,17 +767,22 @@ impl MessageAccumulator {
         }
     }
 
-    pub async fn add(&self, message: Message) -> RabbitMQStreamResult<bool> {
+    /// Queue `message`, returns true if the batch is full
+    async fn add(
+        &self,
+        message: Message,
+        waiter: ProducerMessageWaiter,
+    ) -> Result<bool, ProducerPublishError> {
         self.sender
-            .send(message)
+            .send((message, waiter))
             .await
-            .map_err(|err| ClientError::GenericError(Box::new(err)))?;
+            .map_err(|_| ProducerPublishError::Closed)?;
 
         let val = self.message_count.fetch_add(1, Ordering::Relaxed);
 
         Ok(val + 1 == self.capacity)
     }
-    pub async fn get(&self) -> RabbitMQStreamResult<Option<Message>> {
+    async fn get(&self) -> RabbitMQStreamResult<Option<(Message, ProducerMessageWaiter)>> {
         let mut receiver = self.receiver.lock().await;
         let msg = receiver.try_recv().ok();
 
@@ -234,6 +791,11 @@ impl MessageAccumulator {
         }
         Ok(msg)
     }
+
+    /// Refuse new messages, the ones already queued can still be taken
+    async fn close(&self) {
+        self.receiver.lock().await.close();
+    }
 }
 
 fn schedule_batch_send(producer: Arc<ProducerInternal>, delay: Duration) {
@@ -243,6 +805,9 @@ fn schedule_batch_send(producer: Arc<ProducerInternal>, delay: Duration) {
         debug!("Starting batch send interval every {:?}", delay);
         loop {
             interval.tick().await;
+            if producer.closed.load(Ordering::Relaxed) {
+                break;
+            }
 
             match producer.batch_send().await {
                 Ok(_) => {}
@@ -252,28 +817,42 @@ fn schedule_batch_send(producer: Arc<ProducerInternal>, delay: Duration) {
     });
 }
 
+fn schedule_confirm_timeout(producer: Weak<ProducerInternal>, timeout: Duration) {
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(timeout.min(Duration::from_secs(1)));
+
+        loop {
+            interval.tick().await;
+
+            match producer.upgrade() {
+                Some(producer) if !producer.closed.load(Ordering::Relaxed) => {
+                    producer.expire_waiters(timeout).await
+                }
+                _ => break,
+            }
+        }
+    });
+}
+
 impl<T> Producer<T> {
     pub async fn send_with_confirm(
         &self,
         message: Message,
     ) -> Result<ConfirmationStatus, ProducerPublishError> {
         let (tx, mut rx) = channel(1);
-        let _ = self
-            .internal_send(message, move |status| {
-                let cloned = tx.clone();
-                async move {
-                    let _ = cloned.send(status).await;
-                }
-            })
-            .await?;
+        self.internal_send(message, move |status| {
+            let cloned = tx.clone();
+            async move {
+                let _ = cloned.send(status).await;
+            }
+        })
+        .await?;
 
         rx.recv()
             .await
             .ok_or_else(|| ProducerPublishError::Confirmation {
                 stream: self.0.stream.clone(),
             })?
-            .map_err(|err| ClientError::GenericError(Box::new(err)))
-            .map(Ok)?
     }
 
     pub async fn batch_send_with_confirm(
@@ -283,14 +862,13 @@ impl<T> Producer<T> {
         let messages_len = messages.len();
         let (tx, mut rx) = channel(messages_len);
 
-        let _ = self
-            .internal_batch_send(messages, move |status| {
-                let cloned = tx.clone();
-                async move {
-                    let _ = cloned.send(status).await;
-                }
-            })
-            .await?;
+        self.internal_batch_send(messages, move |status| {
+            let cloned = tx.clone();
+            async move {
+                let _ = cloned.send(status).await;
+            }
+        })
+        .await?;
 
         let mut confirmations = Vec::with_capacity(messages_len);
 
@@ -308,7 +886,7 @@ impl<T> Producer<T> {
     where
         Fut: Future<Output = ()> + Send + Sync + 'static,
     {
-        let _ = self.internal_batch_send(messages, cb).await?;
+        self.internal_batch_send(messages, cb).await?;
 
         Ok(())
     }
@@ -321,13 +899,30 @@ impl<T> Producer<T> {
     where
         Fut: Future<Output = ()> + Send + Sync + 'static,
     {
-        let _ = self.internal_send(message, cb).await?;
+        self.internal_send(message, cb).await?;
         Ok(())
     }
 
+    /// Like [`Producer::send`], but fails with [`ProducerPublishError::MaxInFlight`] instead
+    /// of waiting when too many messages are waiting for confirmation
+    pub async fn try_send<Fut>(
+        &self,
+        message: Message,
+        cb: impl Fn(Result<ConfirmationStatus, ProducerPublishError>) -> Fut + Send + Sync + 'static,
+    ) -> Result<(), ProducerPublishError>
+    where
+        Fut: Future<Output = ()> + Send + Sync + 'static,
+    {
+        if self.is_closed() {
+            return Err(ProducerPublishError::Closed);
+        }
+        self.0.try_acquire_in_flight(1)?;
+        self.enqueue(message, cb).await
+    }
+
     async fn internal_send<Fut>(
         &self,
-        mut message: Message,
+        message: Message,
         cb: impl Fn(Result<ConfirmationStatus, ProducerPublishError>) -> Fut + Send + Sync + 'static,
     ) -> Result<(), ProducerPublishError>
     where
@@ -336,24 +931,44 @@ impl<T> Producer<T> {
         if self.is_closed() {
             return Err(ProducerPublishError::Closed);
         }
-        let publishing_id = match message.publishing_id() {
-            Some(publishing_id) => *publishing_id,
-            None => self.0.publish_sequence.fetch_add(1, Ordering::Relaxed),
-        };
-        message.set_publishing_id(publishing_id);
+        self.0.acquire_in_flight(1).await?;
+        if self.is_closed() {
+            self.0.release_in_flight(1);
+            return Err(ProducerPublishError::Closed);
+        }
+        self.enqueue(message, cb).await
+    }
 
+    /// Add `message` and its waiter to the next batch.
+    ///
+    /// The publishing id is assigned when the batch is sent, a publishing id set
+    /// on the message which is not increasing fails it through `cb`.
+    async fn enqueue<Fut>(
+        &self,
+        message: Message,
+        cb: impl Fn(Result<ConfirmationStatus, ProducerPublishError>) -> Fut + Send + Sync + 'static,
+    ) -> Result<(), ProducerPublishError>
+    where
+        Fut: Future<Output = ()> + Send + Sync + 'static,
+    {
         let waiter = ProducerMessageWaiter::waiter_with_cb(cb);
-        self.0.waiting_confirmations.insert(publishing_id, waiter);
-
-        if self.0.accumulator.add(message).await? {
-            self.0.batch_send().await?;
+        match self.0.accumulator.add(message, waiter).await {
+            Ok(true) => {
+                self.0.batch_send().await?;
+            }
+            Ok(false) => {}
+            // the producer shut down, its accumulator does not take messages anymore
+            Err(err) => {
+                self.0.release_in_flight(1);
+                return Err(err);
+            }
         }
 
         Ok(())
     }
     async fn internal_batch_send<Fut>(
         &self,
-        mut messages: Vec<Message>,
+        messages: Vec<Message>,
         cb: impl Fn(Result<ConfirmationStatus, ProducerPublishError>) -> Fut + Send + Sync + 'static,
     ) -> Result<(), ProducerPublishError>
     where
@@ -365,20 +980,46 @@ impl<T> Producer<T> {
 
         let arc_cb = Arc::new(move |status| cb(status).boxed());
 
-        for message in &mut messages {
-            let waiter = ProducerMessageWaiter::waiter_with_arc_cb(arc_cb.clone());
-            let publishing_id = match message.publishing_id() {
-                Some(publishing_id) => *publishing_id,
-                None => self.0.publish_sequence.fetch_add(1, Ordering::Relaxed),
+        // publish at most max_in_flight messages at a time, waiting for confirms in between
+        let chunk_size = self.0.max_in_flight.unwrap_or(messages.len()).max(1);
+        let mut messages = messages.into_iter();
+        loop {
+            let mut chunk: Vec<Message> = messages.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            self.0.acquire_in_flight(chunk.len()).await?;
+
+            let _publishing = self.0.publish_lock.lock().await;
+            if self.is_closed() {
+                self.0.release_in_flight(chunk.len());
+                return Err(ProducerPublishError::Closed);
+            }
+            // the messages sent before have lower publishing ids, they go first
+            while self.0.send_accumulated().await? > 0 {}
+
+            let publishing_ids: Result<Vec<u64>, _> = chunk
+                .iter_mut()
+                .map(|message| self.0.assign_publishing_id(message))
+                .collect();
+            let publishing_ids = match publishing_ids {
+                Ok(publishing_ids) => publishing_ids,
+                Err(err) => {
+                    self.0.release_in_flight(chunk.len());
+                    return Err(err);
+                }
             };
-            message.set_publishing_id(publishing_id);
 
-            self.0
-                .waiting_confirmations
-                .insert(publishing_id, waiter.clone());
-        }
+            for publishing_id in &publishing_ids {
+                let waiter = ProducerMessageWaiter::waiter_with_arc_cb(arc_cb.clone());
+                self.0.waiting_confirmations.insert(*publishing_id, waiter);
+            }
 
-        self.0.client.publish(self.0.producer_id, messages).await?;
+            if let Err(err) = self.0.publish(chunk).await {
+                self.0.fail_unpublished(&publishing_ids, &err).await;
+                return Err(err.into());
+            }
+        }
 
         Ok(())
     }
@@ -386,43 +1027,115 @@ impl<T> Producer<T> {
     pub fn is_closed(&self) -> bool {
         self.0.closed.load(Ordering::Relaxed)
     }
-    // TODO handle producer state after close
+
+    /// Close the producer right away, the messages not confirmed yet fail
+    /// with [`ProducerPublishError::Closed`]
     pub async fn close(self) -> Result<(), ProducerCloseError> {
         match self
             .0
             .closed
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
         {
-            Ok(false) => {
-                let response = self.0.client.delete_publisher(self.0.producer_id).await?;
-                if response.is_ok() {
-                    Ok(())
-                } else {
-                    Err(ProducerCloseError::Close {
-                        status: response.code().clone(),
-                        stream: self.0.stream.clone(),
-                    })
-                }
-            }
+            Ok(false) => self.0.shutdown().await,
             _ => Err(ProducerCloseError::AlreadyClosed),
         }
     }
+
+    /// Close the producer after publishing the messages of the current batch and waiting
+    /// up to `timeout` for the confirmation of all the messages sent.
+    ///
+    /// The messages still waiting when `timeout` expires are abandoned and fail
+    /// with [`ProducerPublishError::Closed`].
+    pub async fn close_drain(
+        self,
+        timeout: Duration,
+    ) -> Result<ProducerCloseSummary, ProducerCloseError> {
+        if self
+            .0
+            .closed
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(ProducerCloseError::AlreadyClosed);
+        }
+
+        let confirmed = self.0.confirmed_count.load(Ordering::Relaxed);
+        let failed = self.0.failed_count.load(Ordering::Relaxed);
+
+        if self.0.flush().await && !self.0.wait_confirmations(timeout).await {
+            debug!(
+                "Closing producer of stream {} with messages waiting for confirmation",
+                self.0.stream
+            );
+        }
+
+        let summary = ProducerCloseSummary {
+            confirmed: self.0.confirmed_count.load(Ordering::Relaxed) - confirmed,
+            failed: self.0.failed_count.load(Ordering::Relaxed) - failed,
+            abandoned: self.0.waiting_confirmations.len() as u64,
+        };
+        self.0.shutdown().await?;
+        Ok(summary)
+    }
 }
 
 struct ProducerConfirmHandler {
     waiting_confirmations: WaiterMap,
+    all_confirmed: Arc<Notify>,
+    sub_entries: SubEntryMap,
+    in_flight: Option<Arc<Semaphore>>,
+    stored_sequence: Option<Arc<AtomicU64>>,
+    confirmed_count: Arc<AtomicU64>,
+    failed_count: Arc<AtomicU64>,
     metrics_collector: Arc<dyn MetricsCollector>,
+    producer: Weak<ProducerInternal>,
 }
 
 impl ProducerConfirmHandler {
+    /// Ids of the messages confirmed by `publishing_id`, more than one for a sub-entry
+    fn confirmed_ids(&self, publishing_id: u64) -> Vec<u64> {
+        match self.sub_entries.remove(&publishing_id) {
+            Some((_, mut ids)) => {
+                ids.push(publishing_id);
+                ids
+            }
+            None => vec![publishing_id],
+        }
+    }
+
+    /// Compare `publishing_id` to the sequence stored by the broker when the named
+    /// producer connected, see [`ConfirmationStatus::is_duplicate`]
+    fn is_duplicate(&self, publishing_id: u64) -> bool {
+        match &self.stored_sequence {
+            Some(stored_sequence) => {
+                let stored_sequence = stored_sequence.load(Ordering::Relaxed);
+                stored_sequence > 0 && publishing_id <= stored_sequence
+            }
+            None => false,
+        }
+    }
+
     async fn with_waiter(
         &self,
         publishing_id: u64,
         cb: impl FnOnce(ProducerMessageWaiter) -> BoxFuture<'static, ()>,
-    ) {
+    ) -> bool {
         match self.waiting_confirmations.remove(&publishing_id) {
-            Some(confirm_sender) => cb(confirm_sender.1).await,
-            None => todo!(),
+            Some(confirm_sender) => {
+                if let Some(in_flight) = &self.in_flight {
+                    in_flight.add_permits(1);
+                }
+                cb(confirm_sender.1).await;
+                if self.waiting_confirmations.is_empty() {
+                    self.all_confirmed.notify_waiters();
+                }
+                true
+            }
+            // already failed by the confirm timeout or the close of the producer
+            None => {
+                trace!("No message waiting for confirmation {}", publishing_id);
+                false
+            }
         }
     }
 }
@@ -435,16 +1148,23 @@ impl MessageHandler for ProducerConfirmHandler {
                 match response.kind() {
                     ResponseKind::PublishConfirm(confirm) => {
                         trace!("Got publish_confirm for {:?}", confirm.publishing_ids);
-                        let confirm_len = confirm.publishing_ids.len();
+                        let mut confirm_len = 0;
                         for publishing_id in &confirm.publishing_ids {
-                            let id = *publishing_id;
-                            self.with_waiter(*publishing_id, move |waiter| {
-                                async move {
-                                    let _ = waiter.handle_confirm(id).await;
+                            for id in self.confirmed_ids(*publishing_id) {
+                                confirm_len += 1;
+                                let duplicate = self.is_duplicate(id);
+                                let waiting = self
+                                    .with_waiter(id, move |waiter| {
+                                        async move {
+                                            let _ = waiter.handle_confirm(id, duplicate).await;
+                                        }
+                                        .boxed()
+                                    })
+                                    .await;
+                                if waiting {
+                                    self.confirmed_count.fetch_add(1, Ordering::Relaxed);
                                 }
-                                .boxed()
-                            })
-                            .await;
+                            }
                         }
                         self.metrics_collector
                             .publish_confirm(confirm_len as u64)
@@ -453,15 +1173,30 @@ impl MessageHandler for ProducerConfirmHandler {
                     ResponseKind::PublishError(error) => {
                         trace!("Got publish_error  {:?}", error);
                         for err in &error.publishing_errors {
-                            let code = err.error_code.clone();
-                            let id = err.publishing_id;
-                            self.with_waiter(err.publishing_id, move |waiter| {
-                                async move {
-                                    let _ = waiter.handle_error(id, code).await;
+                            for id in self.confirmed_ids(err.publishing_id) {
+                                let code = err.error_code.clone();
+                                let waiting = self
+                                    .with_waiter(id, move |waiter| {
+                                        async move {
+                                            let _ = waiter.handle_error(id, code).await;
+                                        }
+                                        .boxed()
+                                    })
+                                    .await;
+                                if waiting {
+                                    self.failed_count.fetch_add(1, Ordering::Relaxed);
                                 }
-                                .boxed()
-                            })
-                            .await;
+                            }
+                        }
+                    }
+                    ResponseKind::MetadataUpdate(update) => {
+                        if let Some(producer) = self.producer.upgrade() {
+                            if update.stream() == producer.stream
+                                && !producer.closed.load(Ordering::SeqCst)
+                            {
+                                trace!("Stream {} moved, migrating producer", update.stream());
+                                producer.migrate().await;
+                            }
                         }
                     }
                     _ => {}
@@ -469,9 +1204,38 @@ impl MessageHandler for ProducerConfirmHandler {
             }
             Some(Err(error)) => {
                 trace!(?error);
-                // TODO clean all waiting for confirm
+                if let Some(producer) = self.producer.upgrade() {
+                    match error {
+                        ClientError::ConnectionClosedByServer { code, reason } => {
+                            producer
+                                .fail_all_waiters(|_| {
+                                    ClientError::ConnectionClosedByServer {
+                                        code: code.clone(),
+                                        reason: reason.clone(),
+                                    }
+                                    .into()
+                                })
+                                .await
+                        }
+                        _ => {
+                            producer
+                                .fail_all_waiters(|_| producer.connection_lost())
+                                .await
+                        }
+                    }
+                }
+            }
+            None => {
+                if let Some(producer) = self.producer.upgrade() {
+                    producer
+                        .fail_all_waiters(|_| producer.connection_lost())
+                        .await;
+                    if !producer.closed.load(Ordering::SeqCst) {
+                        trace!("Connection lost, recovering producer");
+                        producer.recover().await;
+                    }
+                }
             }
-            None => todo!(),
         }
         Ok(())
     }
@@ -480,6 +1244,7 @@ impl MessageHandler for ProducerConfirmHandler {
 #[derive(Clone)]
 struct ProducerMessageWaiter {
     cb: ConfirmCallback,
+    created: Instant,
 }
 
 impl ProducerMessageWaiter {
@@ -491,18 +1256,25 @@ impl ProducerMessageWaiter {
     {
         Self {
             cb: Arc::new(move |confirm_status| cb(confirm_status).boxed()),
+            created: Instant::now(),
         }
     }
 
     fn waiter_with_arc_cb(confirm_callback: ConfirmCallback) -> Self {
         Self {
             cb: confirm_callback,
+            created: Instant::now(),
         }
     }
-    async fn handle_confirm(self, publishing_id: u64) -> RabbitMQStreamResult<()> {
+
+    async fn handle_failure(self, error: ProducerPublishError) {
+        (self.cb)(Err(error)).await;
+    }
+    async fn handle_confirm(self, publishing_id: u64, duplicate: bool) -> RabbitMQStreamResult<()> {
         (self.cb)(Ok(ConfirmationStatus {
             publishing_id,
             confirmed: true,
+            duplicate,
             status: ResponseCode::Ok,
         }))
         .await;
@@ -516,9 +1288,173 @@ impl ProducerMessageWaiter {
         (self.cb)(Ok(ConfirmationStatus {
             publishing_id,
             confirmed: false,
+            duplicate: false,
             status,
         }))
         .await;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        marker::PhantomData,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, AtomicU8},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use dashmap::DashMap;
+    use rabbitmq_stream_protocol::{compression::Compression, message::Message};
+    use tokio::{
+        net::TcpListener,
+        sync::{Mutex, Notify, RwLock, Semaphore},
+    };
+
+    use super::{
+        schedule_batch_send, Dedup, MessageAccumulator, NoDedup, Producer, ProducerInternal,
+    };
+    use crate::{
+        client::Client,
+        connection_pool::ConnectionPool,
+        environment::{Environment, EnvironmentOptions},
+        error::{ClientError, ProducerPublishError},
+    };
+
+    /// Producer allowing one message in flight, on a connection limited to frames
+    /// of 1024 bytes to which the server never answers
+    async fn producer<T>(batch_size: usize, name: Option<&str>) -> Producer<T> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let _connection = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+        let client = Client::unopened(port, 1024).await;
+
+        let environment = Environment {
+            options: EnvironmentOptions::default(),
+            nodes: Arc::new(vec![]),
+            producer_connections: Arc::new(ConnectionPool::new(256)),
+            consumer_connections: Arc::new(ConnectionPool::new(256)),
+        };
+        let producer = Arc::new(ProducerInternal {
+            environment,
+            client: RwLock::new(client),
+            producer_id: AtomicU8::new(0),
+            batch_size,
+            sub_entry_size: 1,
+            compression: Compression::None,
+            stream: "stream".to_owned(),
+            name: name.map(str::to_owned),
+            publish_sequence: Arc::new(AtomicU64::new(1)),
+            last_publishing_id: std::sync::Mutex::new(None),
+            stored_sequence: Arc::new(AtomicU64::new(0)),
+            waiting_confirmations: Arc::new(DashMap::new()),
+            all_confirmed: Arc::new(Notify::new()),
+            sub_entries: Arc::new(DashMap::new()),
+            max_in_flight: Some(1),
+            in_flight: Some(Arc::new(Semaphore::new(1))),
+            closed: Arc::new(AtomicBool::new(false)),
+            accumulator: MessageAccumulator::new(batch_size),
+            publish_lock: Mutex::new(()),
+            confirmed_count: Arc::new(AtomicU64::new(0)),
+            failed_count: Arc::new(AtomicU64::new(0)),
+        });
+        schedule_batch_send(producer.clone(), Duration::from_millis(10));
+        Producer(producer, PhantomData)
+    }
+
+    fn oversized_message() -> Message {
+        Message::builder().body(vec![0; 2048]).build()
+    }
+
+    fn assert_released<T>(producer: &Producer<T>) {
+        assert!(producer.0.waiting_confirmations.is_empty());
+        assert_eq!(
+            1,
+            producer.0.in_flight.as_ref().unwrap().available_permits()
+        );
+    }
+
+    #[tokio::test]
+    async fn send_with_confirm_fails_when_the_batch_cannot_be_published() {
+        let producer: Producer<NoDedup> = producer(10, None).await;
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            producer.send_with_confirm(oversized_message()),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            result,
+            Err(ProducerPublishError::Client(
+                ClientError::MessageTooLarge { .. }
+            ))
+        ));
+        assert_released(&producer);
+    }
+
+    #[tokio::test]
+    async fn batch_send_with_confirm_fails_when_the_batch_cannot_be_published() {
+        let producer: Producer<NoDedup> = producer(10, None).await;
+
+        let result = producer
+            .batch_send_with_confirm(vec![oversized_message()])
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ProducerPublishError::Client(
+                ClientError::MessageTooLarge { .. }
+            ))
+        ));
+        assert_released(&producer);
+    }
+
+    #[tokio::test]
+    async fn send_waiting_for_in_flight_fails_once_closed() {
+        let producer: Producer<NoDedup> = producer(10, None).await;
+        // the only message allowed in flight is never confirmed
+        producer
+            .send(Message::builder().body("first").build(), |_| async {})
+            .await
+            .unwrap();
+
+        let waiting = tokio::spawn({
+            let producer = producer.clone();
+            async move {
+                producer
+                    .send_with_confirm(Message::builder().body("second").build())
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // the server does not answer the deletion of the publisher
+        let _ = producer.clone().close().await;
+
+        let result = tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(result, Err(ProducerPublishError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn last_publishing_id_is_the_last_one_assigned() {
+        let producer: Producer<Dedup> = producer(1, Some("producer")).await;
+        assert_eq!(None, producer.last_publishing_id());
+
+        producer
+            .send(Message::builder().body("message").build(), |_| async {})
+            .await
+            .unwrap();
+
+        assert_eq!(Some(1), producer.last_publishing_id());
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use tracing::{debug, warn};
+
+use crate::environment::Environment;
+
+/// Policy used by producers and consumers to recover from a lost connection,
+/// and to retry connecting through a load balancer
+#[derive(Clone, Debug)]
+pub struct RecoveryPolicy {
+    pub enabled: bool,
+    /// Delay before the first reconnection attempt
+    pub initial_delay: Duration,
+    /// Upper bound of the exponential backoff between attempts
+    pub max_delay: Duration,
+    /// Give up after this many attempts, `None` means retry forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        RecoveryPolicy {
+            enabled: true,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl RecoveryPolicy {
+    /// Policy which never tries to recover
+    pub fn disabled() -> Self {
+        RecoveryPolicy {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+
+    pub(crate) fn exhausted(&self, attempt: u32) -> bool {
+        matches!(self.max_attempts, Some(max_attempts) if attempt > max_attempts)
+    }
+}
+
+/// Events emitted while a producer or consumer recovers its connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecoveryEvent {
+    /// The connection used by a producer or consumer of `stream` was lost
+    ConnectionLost { stream: String },
+    /// A new recovery attempt is starting
+    Reconnecting { stream: String, attempt: u32 },
+    /// The producer or consumer is attached again to `stream`
+    Recovered { stream: String },
+    /// Recovery gave up, the producer or consumer is now closed
+    Failed { stream: String },
+    /// The server removed the producer or consumer because `stream` moved to other nodes
+    StreamUnavailable { stream: String },
+    /// `stream` was deleted, the producer or consumer is now closed
+    StreamDeleted { stream: String },
+}
+
+#[async_trait::async_trait]
+pub trait RecoveryListener: Send + Sync {
+    async fn on_event(&self, event: RecoveryEvent);
+}
+
+#[async_trait::async_trait]
+impl<T, F> RecoveryListener for T
+where
+    F: Future<Output = ()> + Send,
+    T: Fn(RecoveryEvent) -> F + Send + Sync,
+{
+    async fn on_event(&self, event: RecoveryEvent) {
+        self(event).await
+    }
+}
+
+pub struct NopRecoveryListener {}
+
+#[async_trait::async_trait]
+impl RecoveryListener for NopRecoveryListener {
+    async fn on_event(&self, _event: RecoveryEvent) {}
+}
+
+/// Run `task` until it succeeds, waiting between attempts as configured by `policy`.
+///
+/// Returns `false` if recovery is disabled, all the attempts failed or `closed`
+/// was set by the application in the meantime.
+pub(crate) async fn recover<T, E, Fut>(
+    policy: &RecoveryPolicy,
+    listener: &dyn RecoveryListener,
+    stream: &str,
+    closed: &AtomicBool,
+    task: impl FnMut() -> Fut,
+) -> bool
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: std::fmt::Debug,
+{
+    listener
+        .on_event(RecoveryEvent::ConnectionLost {
+            stream: stream.to_owned(),
+        })
+        .await;
+    retry(policy, listener, stream, closed, task).await
+}
+
+/// Outcome of [`migrate`]
+#[derive(Debug, PartialEq)]
+pub(crate) enum Migration {
+    Recovered,
+    Failed,
+    StreamDeleted,
+}
+
+/// Run `task` once the server removed a producer or consumer of `stream` after a metadata
+/// update, unless the stream was deleted.
+pub(crate) async fn migrate<T, E, Fut>(
+    environment: &Environment,
+    stream: &str,
+    closed: &AtomicBool,
+    task: impl FnMut() -> Fut,
+) -> Migration
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: std::fmt::Debug,
+{
+    let options = &environment.options;
+    let listener = options.recovery_listener.as_ref();
+
+    // the stream may be still there without a leader, retry unless it is known to be deleted
+    if let Ok(false) = environment.stream_exists(stream).await {
+        debug!("Stream {} was deleted", stream);
+        listener
+            .on_event(RecoveryEvent::StreamDeleted {
+                stream: stream.to_owned(),
+            })
+            .await;
+        return Migration::StreamDeleted;
+    }
+
+    listener
+        .on_event(RecoveryEvent::StreamUnavailable {
+            stream: stream.to_owned(),
+        })
+        .await;
+    if retry(&options.recovery_policy, listener, stream, closed, task).await {
+        Migration::Recovered
+    } else {
+        Migration::Failed
+    }
+}
+
+async fn retry<T, E, Fut>(
+    policy: &RecoveryPolicy,
+    listener: &dyn RecoveryListener,
+    stream: &str,
+    closed: &AtomicBool,
+    mut task: impl FnMut() -> Fut,
+) -> bool
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: std::fmt::Debug,
+{
+    if policy.enabled {
+        let mut attempt = 1;
+        while !policy.exhausted(attempt) {
+            tokio::time::sleep(policy.delay(attempt)).await;
+            if closed.load(Ordering::SeqCst) {
+                debug!("Stopping recovery of stream {}, closed", stream);
+                return false;
+            }
+            listener
+                .on_event(RecoveryEvent::Reconnecting {
+                    stream: stream.to_owned(),
+                    attempt,
+                })
+                .await;
+
+            match task().await {
+                Ok(_) => {
+                    debug!("Recovered stream {} after {} attempts", stream, attempt);
+                    listener
+                        .on_event(RecoveryEvent::Recovered {
+                            stream: stream.to_owned(),
+                        })
+                        .await;
+                    return true;
+                }
+                Err(err) => warn!(
+                    "Recovery attempt {} for {} failed {:?}",
+                    attempt, stream, err
+                ),
+            }
+            attempt += 1;
+        }
+    }
+
+    listener
+        .on_event(RecoveryEvent::Failed {
+            stream: stream.to_owned(),
+        })
+        .await;
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU32, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use tokio::sync::Mutex;
+
+    use super::{recover, NopRecoveryListener, RecoveryEvent, RecoveryPolicy};
+
+    #[test]
+    fn recovery_policy_backoff_test() {
+        let policy = RecoveryPolicy::default()
+            .initial_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(500));
+
+        assert_eq!(Duration::from_millis(100), policy.delay(1));
+        assert_eq!(Duration::from_millis(200), policy.delay(2));
+        assert_eq!(Duration::from_millis(400), policy.delay(3));
+        assert_eq!(Duration::from_millis(500), policy.delay(4));
+        assert_eq!(Duration::from_millis(500), policy.delay(100));
+    }
+
+    #[tokio::test]
+    async fn recover_retries_until_success_test() {
+        let policy = RecoveryPolicy::default().initial_delay(Duration::from_millis(1));
+        let events = Arc::new(Mutex::new(vec![]));
+        let attempts = AtomicU32::new(0);
+        let closed = AtomicBool::new(false);
+
+        let cloned = events.clone();
+        let listener = move |event| {
+            let events = cloned.clone();
+            async move { events.lock().await.push(event) }
+        };
+
+        let recovered = recover(&policy, &listener, "stream", &closed, || async {
+            match attempts.fetch_add(1, Ordering::Relaxed) {
+                0 => Err("failed"),
+                _ => Ok(()),
+            }
+        })
+        .await;
+
+        assert!(recovered);
+        assert_eq!(
+            vec![
+                RecoveryEvent::ConnectionLost {
+                    stream: "stream".to_owned()
+                },
+                RecoveryEvent::Reconnecting {
+                    stream: "stream".to_owned(),
+                    attempt: 1
+                },
+                RecoveryEvent::Reconnecting {
+                    stream: "stream".to_owned(),
+                    attempt: 2
+                },
+                RecoveryEvent::Recovered {
+                    stream: "stream".to_owned()
+                },
+            ],
+            *events.lock().await
+        );
+    }
+
+    #[tokio::test]
+    async fn recover_gives_up_test() {
+        let policy = RecoveryPolicy::default()
+            .initial_delay(Duration::from_millis(1))
+            .max_attempts(3);
+        let attempts = AtomicU32::new(0);
+        let closed = AtomicBool::new(false);
+
+        let recovered = recover(
+            &policy,
+            &NopRecoveryListener {},
+            "stream",
+            &closed,
+            || async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                Err::<(), _>("failed")
+            },
+        )
+        .await;
+
+        assert!(!recovered);
+        assert_eq!(3, attempts.load(Ordering::Relaxed));
+
+        let recovered = recover(
+            &RecoveryPolicy::disabled(),
+            &NopRecoveryListener {},
+            "stream",
+            &closed,
+            || async { Ok::<_, ()>(()) },
+        )
+        .await;
+
+        assert!(!recovered);
+    }
+
+    #[tokio::test]
+    async fn recover_stops_once_closed_test() {
+        let policy = RecoveryPolicy::default().initial_delay(Duration::from_millis(1));
+        let attempts = AtomicU32::new(0);
+        let closed = AtomicBool::new(false);
+
+        // the application closes during the first attempt
+        let recovered = recover(
+            &policy,
+            &NopRecoveryListener {},
+            "stream",
+            &closed,
+            || async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                closed.store(true, Ordering::SeqCst);
+                Err::<(), _>("failed")
+            },
+        )
+        .await;
+
+        assert!(!recovered);
+        assert_eq!(1, attempts.load(Ordering::Relaxed));
+    }
+}
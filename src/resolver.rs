@@ -0,0 +1,42 @@
+use crate::client::Broker;
+
+/// Maps the nodes advertised by the server to the addresses the client can reach,
+/// e.g. when the brokers run in containers or behind a NAT.
+pub trait AddressResolver: Send + Sync {
+    fn resolve(&self, broker: &Broker) -> Broker;
+}
+
+impl<T> AddressResolver for T
+where
+    T: Fn(&Broker) -> Broker + Send + Sync,
+{
+    fn resolve(&self, broker: &Broker) -> Broker {
+        self(broker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AddressResolver;
+    use crate::client::Broker;
+
+    #[test]
+    fn closure_resolver_test() {
+        let resolver = |broker: &Broker| Broker {
+            host: "localhost".to_owned(),
+            port: broker.port + 1000,
+        };
+        let resolver: &dyn AddressResolver = &resolver;
+
+        assert_eq!(
+            Broker {
+                host: "localhost".to_owned(),
+                port: 6552,
+            },
+            resolver.resolve(&Broker {
+                host: "node-1".to_owned(),
+                port: 5552,
+            })
+        );
+    }
+}
@@ -34,6 +34,47 @@ impl StreamCreator {
         }
     }
 
+    /// Create a super stream with `partitions` partitions, named `<super_stream>-<n>`
+    /// and bound with the keys `0` to `partitions - 1`
+    pub async fn create_super_stream(
+        self,
+        super_stream: &str,
+        partitions: usize,
+    ) -> Result<(), StreamCreateError> {
+        let binding_keys = (0..partitions)
+            .map(|partition| partition.to_string())
+            .collect();
+        self.create_super_stream_with_binding_keys(super_stream, binding_keys)
+            .await
+    }
+
+    /// Create a super stream with a partition `<super_stream>-<binding key>` for each binding key
+    pub async fn create_super_stream_with_binding_keys(
+        self,
+        super_stream: &str,
+        binding_keys: Vec<String>,
+    ) -> Result<(), StreamCreateError> {
+        let partitions = binding_keys
+            .iter()
+            .map(|key| format!("{}-{}", super_stream, key))
+            .collect();
+
+        let client = self.env.create_client().await?;
+        let response = client
+            .create_super_stream(super_stream, partitions, binding_keys, self.options)
+            .await?;
+        client.close().await?;
+
+        if response.is_ok() {
+            Ok(())
+        } else {
+            Err(StreamCreateError::Create {
+                stream: super_stream.to_owned(),
+                status: response.code().clone(),
+            })
+        }
+    }
+
     pub fn max_age(mut self, max_age: Duration) -> Self {
         self.options
             .insert("max-age".to_owned(), format!("{}s", max_age.as_secs()));
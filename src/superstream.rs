@@ -0,0 +1,182 @@
+use std::{convert::TryInto, sync::Arc};
+
+use dashmap::DashMap;
+use rabbitmq_stream_protocol::message::Message;
+
+use crate::{client::Client, error::ClientError, RabbitMQStreamResult};
+
+/// Seed used by the other RabbitMQ stream clients, so that every client
+/// routes a routing key to the same partition
+const HASH_SEED: u32 = 104729;
+
+pub type RoutingKeyExtractor = Arc<dyn Fn(&Message) -> String + Send + Sync>;
+
+/// How a super stream producer picks the partitions of a message
+#[derive(Clone)]
+pub enum RoutingStrategy {
+    /// Hash the routing key with murmur3 and pick one partition out of all of them
+    Hash(RoutingKeyExtractor),
+    /// Ask the broker for the partitions bound to the routing key
+    Key(RoutingKeyExtractor),
+}
+
+impl RoutingStrategy {
+    /// Check if the routes have to be asked to the broker
+    pub(crate) fn needs_client(&self) -> bool {
+        matches!(self, RoutingStrategy::Key(_))
+    }
+
+    pub fn hash(extractor: impl Fn(&Message) -> String + Send + Sync + 'static) -> Self {
+        RoutingStrategy::Hash(Arc::new(extractor))
+    }
+
+    pub fn key(extractor: impl Fn(&Message) -> String + Send + Sync + 'static) -> Self {
+        RoutingStrategy::Key(Arc::new(extractor))
+    }
+}
+
+/// Resolves the partitions of a super stream for each message
+pub(crate) struct Router {
+    strategy: RoutingStrategy,
+    super_stream: String,
+    partitions: Vec<String>,
+    routes: DashMap<String, Vec<String>>,
+    /// Client asking the routes, only set for [`RoutingStrategy::Key`]
+    client: Option<Client>,
+}
+
+impl Router {
+    pub(crate) fn new(
+        strategy: RoutingStrategy,
+        super_stream: &str,
+        partitions: Vec<String>,
+        client: Option<Client>,
+    ) -> Router {
+        Router {
+            strategy,
+            super_stream: super_stream.to_owned(),
+            partitions,
+            routes: DashMap::new(),
+            client,
+        }
+    }
+
+    pub(crate) fn partitions(&self) -> &[String] {
+        &self.partitions
+    }
+
+    pub(crate) async fn route(&self, message: &Message) -> RabbitMQStreamResult<Vec<String>> {
+        match &self.strategy {
+            RoutingStrategy::Hash(extractor) => Ok(self
+                .hash_route(&extractor(message))
+                .into_iter()
+                .cloned()
+                .collect()),
+            RoutingStrategy::Key(extractor) => {
+                let routing_key = extractor(message);
+                if let Some(streams) = self.routes.get(&routing_key) {
+                    return Ok(streams.clone());
+                }
+                let client = self.client.as_ref().ok_or(ClientError::ConnectionClosed)?;
+                let streams = client.route(&routing_key, &self.super_stream).await?;
+                self.routes.insert(routing_key, streams.clone());
+                Ok(streams)
+            }
+        }
+    }
+
+    /// Close the client asking the routes, if any
+    pub(crate) async fn close(&self) -> RabbitMQStreamResult<()> {
+        match &self.client {
+            Some(client) => client.close().await,
+            None => Ok(()),
+        }
+    }
+
+    fn hash_route(&self, routing_key: &str) -> Option<&String> {
+        if self.partitions.is_empty() {
+            return None;
+        }
+        let hash = murmur3_32(routing_key.as_bytes(), HASH_SEED);
+        self.partitions.get(hash as usize % self.partitions.len())
+    }
+}
+
+/// 32 bits MurmurHash3 (x86 variant)
+fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mut hash = seed;
+    let mut chunks = data.chunks_exact(4);
+
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        hash ^= k;
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .rev()
+            .fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{murmur3_32, Router, RoutingStrategy};
+
+    #[test]
+    fn murmur3_test() {
+        assert_eq!(0, murmur3_32(b"", 0));
+        assert_eq!(0x514e_28b7, murmur3_32(b"", 1));
+        assert_eq!(0x248b_fa47, murmur3_32(b"hello", 0));
+        assert_eq!(
+            0x2e4f_f723,
+            murmur3_32(b"The quick brown fox jumps over the lazy dog", 0)
+        );
+    }
+
+    #[test]
+    fn hash_route_test() {
+        let partitions = vec![
+            "invoices-0".to_owned(),
+            "invoices-1".to_owned(),
+            "invoices-2".to_owned(),
+        ];
+        let router = Router::new(
+            RoutingStrategy::hash(|_| String::new()),
+            "invoices",
+            partitions,
+            None,
+        );
+
+        assert_eq!(Some(&"invoices-0".to_owned()), router.hash_route("hello"));
+        assert_eq!(Some(&"invoices-1".to_owned()), router.hash_route("2"));
+        assert_eq!(Some(&"invoices-2".to_owned()), router.hash_route("0"));
+
+        let empty = Router::new(
+            RoutingStrategy::hash(|_| String::new()),
+            "empty",
+            vec![],
+            None,
+        );
+        assert_eq!(None, empty.hash_route("hello"));
+    }
+}
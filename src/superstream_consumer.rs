@@ -0,0 +1,227 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::{stream::SelectAll, FutureExt, Stream};
+use rabbitmq_stream_protocol::commands::subscribe::OffsetSpecification;
+
+use crate::{
+    consumer::{
+        Consumer, ConsumerBuilder, ConsumerHandle, ConsumerUpdateContext, ConsumerUpdateListener,
+        Delivery, EndSpecification, OffsetTracking,
+    },
+    credit::CreditStrategy,
+    environment::Environment,
+    error::{ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError},
+};
+
+/// API for consuming the partitions of a super stream as a single stream of deliveries.
+///
+/// Use [`Delivery::stream`] to know which partition a message comes from.
+pub struct SuperStreamConsumer {
+    consumers: SelectAll<Consumer>,
+    handle: SuperStreamConsumerHandle,
+}
+
+/// Builder for [`SuperStreamConsumer`]
+pub struct SuperStreamConsumerBuilder {
+    pub environment: Environment,
+    pub offset_specification: OffsetSpecification,
+    pub name: Option<String>,
+    pub offset_tracking: OffsetTracking,
+    pub single_active_consumer: bool,
+    pub crc_check: bool,
+    pub end_specification: Option<EndSpecification>,
+    pub initial_credits: u16,
+    pub buffer_size: usize,
+    pub credit_strategy: Arc<dyn CreditStrategy>,
+    pub(crate) consumer_update_listener: Option<ConsumerUpdateListener>,
+}
+
+impl SuperStreamConsumerBuilder {
+    pub async fn build(
+        self,
+        super_stream: &str,
+    ) -> Result<SuperStreamConsumer, ConsumerCreateError> {
+        let client = self.environment.create_client().await?;
+        let partitions = client.partitions(super_stream).await?;
+        client.close().await?;
+
+        if partitions.is_empty() {
+            return Err(ConsumerCreateError::StreamDoesNotExist {
+                stream: super_stream.to_owned(),
+            });
+        }
+
+        let mut consumers = SelectAll::new();
+        let mut handles: Vec<ConsumerHandle> = Vec::with_capacity(partitions.len());
+        for partition in &partitions {
+            let consumer = ConsumerBuilder {
+                environment: self.environment.clone(),
+                offset_specification: self.offset_specification.clone(),
+                name: self.name.clone(),
+                offset_tracking: self.offset_tracking.clone(),
+                single_active_consumer: self.single_active_consumer,
+                crc_check: self.crc_check,
+                end_specification: self.end_specification.clone(),
+                initial_credits: self.initial_credits,
+                buffer_size: self.buffer_size,
+                credit_strategy: self.credit_strategy.clone(),
+                super_stream: Some(super_stream.to_owned()),
+                consumer_update_listener: self.consumer_update_listener.clone(),
+            }
+            .build(partition)
+            .await;
+            let consumer = match consumer {
+                Ok(consumer) => consumer,
+                Err(err) => {
+                    // close the consumers of the partitions already built
+                    for handle in handles {
+                        let _ = handle.close().await;
+                    }
+                    return Err(err);
+                }
+            };
+            handles.push(consumer.handle());
+            consumers.push(consumer);
+        }
+
+        Ok(SuperStreamConsumer {
+            consumers,
+            handle: SuperStreamConsumerHandle(handles),
+        })
+    }
+
+    pub fn offset(mut self, offset_specification: OffsetSpecification) -> Self {
+        self.offset_specification = offset_specification;
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// How the offset of each partition is stored, see [`ConsumerBuilder::offset_tracking`]
+    pub fn offset_tracking(mut self, offset_tracking: OffsetTracking) -> Self {
+        self.offset_tracking = offset_tracking;
+        self
+    }
+
+    /// Each partition is consumed by only one of the consumers with the same name,
+    /// see [`ConsumerBuilder::single_active_consumer`]
+    pub fn single_active_consumer(mut self, single_active_consumer: bool) -> Self {
+        self.single_active_consumer = single_active_consumer;
+        self
+    }
+
+    /// Verify the CRC of the chunks of each partition, see [`ConsumerBuilder::crc_check`]
+    pub fn crc_check(mut self, crc_check: bool) -> Self {
+        self.crc_check = crc_check;
+        self
+    }
+
+    /// Stop consuming each partition at `end_specification`, see [`ConsumerBuilder::until`]
+    pub fn until(mut self, end_specification: EndSpecification) -> Self {
+        self.end_specification = Some(end_specification);
+        self
+    }
+
+    /// Credits given to the server by the consumer of each partition,
+    /// see [`ConsumerBuilder::initial_credits`]
+    pub fn initial_credits(mut self, initial_credits: u16) -> Self {
+        self.initial_credits = initial_credits;
+        self
+    }
+
+    /// Number of messages of each partition waiting to be consumed,
+    /// see [`ConsumerBuilder::buffer_size`]
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// When the consumer of each partition grants credits to the server,
+    /// see [`ConsumerBuilder::credit_strategy`]
+    pub fn credit_strategy(mut self, credit_strategy: impl CreditStrategy + 'static) -> Self {
+        self.credit_strategy = Arc::new(credit_strategy);
+        self
+    }
+
+    /// Listener called when the consumer of a partition is promoted or demoted,
+    /// see [`ConsumerBuilder::consumer_update`]
+    pub fn consumer_update<Fut>(
+        mut self,
+        listener: impl Fn(ConsumerUpdateContext) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        Fut: Future<Output = OffsetSpecification> + Send + 'static,
+    {
+        self.consumer_update_listener = Some(Arc::new(move |context| listener(context).boxed()));
+        self
+    }
+}
+
+impl SuperStreamConsumer {
+    /// Return an handle for current [`SuperStreamConsumer`]
+    pub fn handle(&self) -> SuperStreamConsumerHandle {
+        self.handle.clone()
+    }
+}
+
+impl Stream for SuperStreamConsumer {
+    type Item = Result<Delivery, ConsumerDeliveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.consumers).poll_next(cx)
+    }
+}
+
+/// Handler API for [`SuperStreamConsumer`]
+#[derive(Clone)]
+pub struct SuperStreamConsumerHandle(Vec<ConsumerHandle>);
+
+impl SuperStreamConsumerHandle {
+    /// Close the consumers of all the partitions
+    pub async fn close(self) -> Result<(), ConsumerCloseError> {
+        let mut result = Ok(());
+        for handle in self.0 {
+            if let Err(err) = handle.close().await {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+
+    /// Pause the consumers of all the partitions, see [`ConsumerHandle::pause`]
+    pub fn pause(&self) {
+        for handle in &self.0 {
+            handle.pause();
+        }
+    }
+
+    /// Resume the consumers of all the partitions
+    pub async fn resume(&self) {
+        for handle in &self.0 {
+            handle.resume().await;
+        }
+    }
+
+    /// Check if the consumers of all the partitions are paused
+    pub fn is_paused(&self) -> bool {
+        self.0.iter().all(|handle| handle.is_paused())
+    }
+
+    /// Check if the consumers of all the partitions are closed
+    pub async fn is_closed(&self) -> bool {
+        for handle in &self.0 {
+            if !handle.is_closed().await {
+                return false;
+            }
+        }
+        true
+    }
+}
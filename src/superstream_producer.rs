@@ -0,0 +1,225 @@
+use std::{collections::HashMap, future::Future, marker::PhantomData, sync::Arc, time::Duration};
+
+use rabbitmq_stream_protocol::{compression::Compression, message::Message};
+use tokio::sync::Semaphore;
+
+use crate::{
+    environment::Environment,
+    error::{ProducerCloseError, ProducerCreateError, ProducerPublishError},
+    producer::{ConfirmationStatus, Dedup, Producer, ProducerBuilder},
+    superstream::{Router, RoutingStrategy},
+};
+
+/// API for publishing messages to the partitions of a super stream
+pub struct SuperStreamProducer<T>(Arc<SuperStreamProducerInternal<T>>);
+
+struct SuperStreamProducerInternal<T> {
+    super_stream: String,
+    router: Router,
+    producers: HashMap<String, Producer<T>>,
+}
+
+impl<T> Clone for SuperStreamProducer<T> {
+    fn clone(&self) -> Self {
+        SuperStreamProducer(self.0.clone())
+    }
+}
+
+/// Builder for [`SuperStreamProducer`]
+pub struct SuperStreamProducerBuilder<T> {
+    pub environment: Environment,
+    pub name: Option<String>,
+    pub batch_size: usize,
+    pub batch_publishing_delay: Duration,
+    pub sub_entry_size: usize,
+    pub compression: Compression,
+    pub max_in_flight: Option<usize>,
+    pub confirm_timeout: Option<Duration>,
+    pub routing_strategy: RoutingStrategy,
+    pub data: PhantomData<T>,
+}
+
+impl<T> SuperStreamProducerBuilder<T> {
+    pub async fn build(
+        self,
+        super_stream: &str,
+    ) -> Result<SuperStreamProducer<T>, ProducerCreateError> {
+        let client = self.environment.create_client().await?;
+        let partitions = client.partitions(super_stream).await?;
+
+        if partitions.is_empty() {
+            client.close().await?;
+            return Err(ProducerCreateError::StreamDoesNotExist {
+                stream: super_stream.to_owned(),
+            });
+        }
+
+        // the client is only needed afterwards to ask the routes of the routing keys
+        let client = if self.routing_strategy.needs_client() {
+            Some(client)
+        } else {
+            client.close().await?;
+            None
+        };
+        let router = Router::new(self.routing_strategy, super_stream, partitions, client);
+
+        let mut producers = HashMap::with_capacity(router.partitions().len());
+        for partition in router.partitions() {
+            let producer = ProducerBuilder {
+                environment: self.environment.clone(),
+                name: self.name.clone(),
+                batch_size: self.batch_size,
+                batch_publishing_delay: self.batch_publishing_delay,
+                sub_entry_size: self.sub_entry_size,
+                compression: self.compression,
+                max_in_flight: self.max_in_flight,
+                confirm_timeout: self.confirm_timeout,
+                data: PhantomData,
+            }
+            .build(partition)
+            .await;
+            match producer {
+                Ok(producer) => {
+                    producers.insert(partition.clone(), producer);
+                }
+                Err(err) => {
+                    // close the producers of the partitions already built
+                    for (_, producer) in producers {
+                        let _ = producer.close().await;
+                    }
+                    let _ = router.close().await;
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(SuperStreamProducer(Arc::new(SuperStreamProducerInternal {
+            super_stream: super_stream.to_owned(),
+            router,
+            producers,
+        })))
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn batch_delay(mut self, delay: Duration) -> Self {
+        self.batch_publishing_delay = delay;
+        self
+    }
+
+    /// Group the messages of each partition in sub-entries, see [`ProducerBuilder::sub_entry_size`]
+    pub fn sub_entry_size(mut self, sub_entry_size: usize) -> Self {
+        self.sub_entry_size = sub_entry_size.clamp(1, u16::MAX as usize);
+        self
+    }
+
+    /// Compression of the sub-entries, used only when `sub_entry_size` is greater than 1
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Limit the messages waiting for confirmation of each partition, see [`ProducerBuilder::max_in_flight`]
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.clamp(1, Semaphore::MAX_PERMITS));
+        self
+    }
+
+    /// Fail the messages of each partition not confirmed after `timeout`, see [`ProducerBuilder::confirm_timeout`]
+    pub fn confirm_timeout(mut self, timeout: Duration) -> Self {
+        self.confirm_timeout = Some(timeout);
+        self
+    }
+
+    /// Name of the producer of each partition, enables deduplication
+    pub fn name(self, name: &str) -> SuperStreamProducerBuilder<Dedup> {
+        SuperStreamProducerBuilder {
+            environment: self.environment,
+            name: Some(name.to_owned()),
+            batch_size: self.batch_size,
+            batch_publishing_delay: self.batch_publishing_delay,
+            sub_entry_size: self.sub_entry_size,
+            compression: self.compression,
+            max_in_flight: self.max_in_flight,
+            confirm_timeout: self.confirm_timeout,
+            routing_strategy: self.routing_strategy,
+            data: PhantomData,
+        }
+    }
+}
+
+impl<T> SuperStreamProducer<T> {
+    /// Send `message` to the partitions picked by the routing strategy.
+    ///
+    /// `cb` is called once for each partition the message is sent to.
+    pub async fn send<Fut>(
+        &self,
+        message: Message,
+        cb: impl Fn(Result<ConfirmationStatus, ProducerPublishError>) -> Fut + Send + Sync + 'static,
+    ) -> Result<(), ProducerPublishError>
+    where
+        Fut: Future<Output = ()> + Send + Sync + 'static,
+    {
+        let cb = Arc::new(cb);
+        for producer in self.route(&message).await? {
+            let cb = cb.clone();
+            producer
+                .send(message.clone(), move |status| cb(status))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send `message` and wait for the confirmation of each partition it was sent to
+    pub async fn send_with_confirm(
+        &self,
+        message: Message,
+    ) -> Result<Vec<ConfirmationStatus>, ProducerPublishError> {
+        let producers = self.route(&message).await?;
+        let mut confirmations = Vec::with_capacity(producers.len());
+        for producer in producers {
+            confirmations.push(producer.send_with_confirm(message.clone()).await?);
+        }
+        Ok(confirmations)
+    }
+
+    /// Partitions of the super stream
+    pub fn partitions(&self) -> &[String] {
+        self.0.router.partitions()
+    }
+
+    /// Close the producers of all the partitions
+    pub async fn close(self) -> Result<(), ProducerCloseError> {
+        let mut result = Ok(());
+        for producer in self.0.producers.values() {
+            if let Err(err) = producer.clone().close().await {
+                result = result.and(Err(err));
+            }
+        }
+        if let Err(err) = self.0.router.close().await {
+            result = result.and(Err(err.into()));
+        }
+        result
+    }
+
+    async fn route(&self, message: &Message) -> Result<Vec<&Producer<T>>, ProducerPublishError> {
+        let producers: Vec<_> = self
+            .0
+            .router
+            .route(message)
+            .await?
+            .iter()
+            .filter_map(|partition| self.0.producers.get(partition))
+            .collect();
+
+        if producers.is_empty() {
+            return Err(ProducerPublishError::Unroutable {
+                super_stream: self.0.super_stream.clone(),
+            });
+        }
+        Ok(producers)
+    }
+}
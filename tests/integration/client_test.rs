@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use fake::{Fake, Faker};
 use rabbitmq_stream_client::{
+    error::ClientError,
     types::{
         Broker, Message, MessageResult, OffsetSpecification, ResponseCode, ResponseKind,
         StreamMetadata,
@@ -55,6 +56,15 @@ async fn client_delete_stream_error_test() {
     assert_eq!(&ResponseCode::StreamDoesNotExist, response.code());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn client_stream_exists_test() {
+    let test = TestClient::create().await;
+    let stream: String = Faker.fake();
+
+    assert!(test.client.stream_exists(&test.stream).await.unwrap());
+    assert!(!test.client.stream_exists(&stream).await.unwrap());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn client_metadata_test() {
     let test = TestClient::create().await;
@@ -182,11 +192,8 @@ async fn client_publish() {
 
     let handler = move |msg: MessageResult| async move {
         if let Some(Ok(response)) = msg {
-            match response.kind() {
-                ResponseKind::Deliver(delivery) => {
-                    tx.send(delivery.clone()).await.unwrap();
-                }
-                _ => {}
+            if let ResponseKind::Deliver(delivery) = response.kind() {
+                tx.send(delivery.clone()).await.unwrap();
             }
         }
         Ok(())
@@ -227,6 +234,33 @@ async fn client_publish() {
     assert_eq!(1, delivery.messages.len());
     assert_eq!(
         Some(b"message".as_ref()),
-        delivery.messages.get(0).unwrap().data()
+        delivery.messages.first().unwrap().data()
     );
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_publish_split_in_frames() {
+    let test = TestClient::create().await;
+    let reference: String = Faker.fake();
+
+    let _ = test
+        .client
+        .declare_publisher(1, Some(reference.clone()), &test.stream)
+        .await
+        .unwrap();
+
+    // together the messages exceed the default max frame size of 1MB
+    let messages: Vec<Message> = (0..20)
+        .map(|_| Message::builder().body(vec![0; 100 * 1024]).build())
+        .collect();
+    let sequences = test.client.publish(1, messages).await.unwrap();
+    assert_eq!(20, sequences.len());
+
+    let result = test
+        .client
+        .publish(1, Message::builder().body(vec![0; 2 * 1024 * 1024]).build())
+        .await;
+    assert!(matches!(result, Err(ClientError::MessageTooLarge { .. })));
+
+    let _ = test.client.delete_publisher(1).await.unwrap();
+}
@@ -4,8 +4,12 @@ use crate::common::TestEnvironment;
 use fake::{Fake, Faker};
 use futures::StreamExt;
 use rabbitmq_stream_client::{
-    error::{ConsumerCloseError, ProducerCloseError},
-    types::{Message, OffsetSpecification},
+    error::{
+        ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError, ConsumerStoreOffsetError,
+        ProducerCloseError,
+    },
+    types::{EndSpecification, Message, OffsetSpecification},
+    ChunkConsumedCredit, Environment, OffsetTracking,
 };
 
 #[tokio::test(flavor = "multi_thread")]
@@ -47,6 +51,246 @@ async fn consumer_test() {
     producer.close().await.unwrap();
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_chunks_test() {
+    let env = TestEnvironment::create().await;
+
+    let message_count = 10;
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    let messages = (0..message_count)
+        .map(|n| Message::builder().body(format!("message{}", n)).build())
+        .collect();
+    producer.batch_send_with_confirm(messages).await.unwrap();
+
+    let mut chunks = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap()
+        .chunks();
+
+    let mut received = 0;
+    while received < message_count {
+        let chunk = chunks.next().await.unwrap().unwrap();
+        let first = chunk.first().unwrap();
+        assert_eq!(first.offset(), first.chunk_id());
+        assert!(first.chunk_timestamp() <= std::time::SystemTime::now());
+        for delivery in &chunk {
+            assert_eq!(first.chunk_id(), delivery.chunk_id());
+            assert_eq!(first.epoch(), delivery.epoch());
+        }
+        received += chunk.len();
+    }
+    assert_eq!(message_count, received);
+
+    chunks.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_crc_check_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    let mut consumer = env
+        .env
+        .consumer()
+        .crc_check(true)
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(b"message".as_ref()), delivery.message().data());
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_chunk_consumed_credit_test() {
+    let env = TestEnvironment::create().await;
+
+    let message_count = 5;
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    // each message confirmed before the next one is sent lands in its own chunk
+    for n in 0..message_count {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .initial_credits(1)
+        .buffer_size(1)
+        .credit_strategy(ChunkConsumedCredit)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for n in 0..message_count {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(n, delivery.offset());
+    }
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_many_credits_keeps_order_test() {
+    let env = TestEnvironment::create().await;
+
+    let message_count = 50;
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    // each message confirmed before the next one is sent lands in its own chunk
+    for n in 0..message_count {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .initial_credits(10)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let mut last_offset = None;
+    for _ in 0..message_count {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert!(last_offset < Some(delivery.offset()));
+        last_offset = Some(delivery.offset());
+    }
+    assert_eq!(Some(message_count - 1), last_offset);
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_pause_resume_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .initial_credits(1)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let handle = consumer.handle();
+    handle.pause();
+    assert!(handle.is_paused());
+
+    // each message confirmed before the next one is sent lands in its own chunk
+    for n in 0..2 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    // the initial credit is already granted, the next chunk is not requested while paused
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(0, delivery.offset());
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), consumer.next())
+            .await
+            .is_err()
+    );
+
+    handle.resume().await;
+    assert!(!handle.is_paused());
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(1, delivery.offset());
+
+    handle.close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_until_test() {
+    let env = TestEnvironment::create().await;
+
+    let message_count = 5;
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    for n in 0..message_count {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    let consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .until(EndSpecification::CurrentTail)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let offsets: Vec<u64> = consumer
+        .map(|delivery| delivery.unwrap().offset())
+        .collect()
+        .await;
+    assert_eq!(vec![0, 1, 2, 3, 4], offsets);
+
+    let consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::Offset(1))
+        .until(EndSpecification::Offset(3))
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let offsets: Vec<u64> = consumer
+        .map(|delivery| delivery.unwrap().offset())
+        .collect()
+        .await;
+    assert_eq!(vec![1, 2, 3], offsets);
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_stream_deleted_test() {
+    let stream: String = Faker.fake();
+    let env = Environment::builder().build().await.unwrap();
+    env.stream_creator().create(&stream).await.unwrap();
+
+    let mut consumer = env.consumer().build(&stream).await.unwrap();
+    env.delete_stream(&stream).await.unwrap();
+
+    let delivery = consumer.next().await.unwrap();
+    assert!(matches!(
+        delivery,
+        Err(ConsumerDeliveryError::StreamDeleted { .. })
+    ));
+    assert!(consumer.next().await.is_none());
+    assert!(consumer.is_closed());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn consumer_close_test() {
     let env = TestEnvironment::create().await;
@@ -69,7 +313,7 @@ async fn consumer_close_test() {
     let handle = consumer.handle();
     let delivery = consumer.next().await;
 
-    assert_eq!(false, consumer.is_closed());
+    assert!(!consumer.is_closed());
     assert!(delivery.is_some());
 
     tokio::spawn(async move {
@@ -80,7 +324,7 @@ async fn consumer_close_test() {
     let delivery = consumer.next().await;
 
     assert!(delivery.is_none());
-    assert_eq!(true, consumer.is_closed());
+    assert!(consumer.is_closed());
 
     assert!(matches!(
         consumer.handle().close().await,
@@ -93,3 +337,230 @@ async fn consumer_close_test() {
         Err(ProducerCloseError::AlreadyClosed),
     ));
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn single_active_consumer_test() {
+    let env = TestEnvironment::create().await;
+    let reference: String = Faker.fake();
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+
+    let (update_tx, mut update_rx) = tokio::sync::mpsc::channel(2);
+    let build_consumer = || {
+        let update_tx = update_tx.clone();
+        env.env
+            .consumer()
+            .name(&reference)
+            .single_active_consumer(true)
+            .offset(OffsetSpecification::First)
+            .consumer_update(move |context| {
+                let update_tx = update_tx.clone();
+                async move {
+                    let _ = update_tx.send(context.is_active()).await;
+                    OffsetSpecification::First
+                }
+            })
+            .build(&env.stream)
+    };
+
+    let mut active = build_consumer().await.unwrap();
+    let mut passive = build_consumer().await.unwrap();
+
+    assert_eq!(Some(true), update_rx.recv().await);
+
+    producer
+        .send_with_confirm(Message::builder().body("message").build())
+        .await
+        .unwrap();
+
+    assert!(active.next().await.unwrap().is_ok());
+
+    active.handle().close().await.unwrap();
+
+    // the passive consumer is promoted and starts from the first message
+    assert_eq!(Some(true), update_rx.recv().await);
+    let delivery = passive.next().await.unwrap().unwrap();
+    assert_eq!(0, delivery.offset());
+
+    passive.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn single_active_consumer_without_name_test() {
+    let env = TestEnvironment::create().await;
+
+    let consumer = env
+        .env
+        .consumer()
+        .single_active_consumer(true)
+        .build(&env.stream)
+        .await;
+
+    assert!(matches!(
+        consumer,
+        Err(ConsumerCreateError::SingleActiveConsumerWithoutName { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_automatic_offset_tracking_resumes_test() {
+    let env = TestEnvironment::create().await;
+    let name: String = Faker.fake();
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    for n in 0..10 {
+        let _ = producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .offset_tracking(OffsetTracking::automatic())
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for _ in 0..5 {
+        consumer.next().await.unwrap().unwrap();
+    }
+    // the offset of the last consumed message is stored on close
+    consumer.handle().close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(b"message5".as_ref()), delivery.message().data());
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_manual_offset_tracking_test() {
+    let env = TestEnvironment::create().await;
+    let name: String = Faker.fake();
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    for n in 0..10 {
+        let _ = producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    let consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .offset_tracking(OffsetTracking::Manual)
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    consumer.store_offset(4).await.unwrap();
+    consumer.handle().close().await.unwrap();
+
+    let stored = env
+        .env
+        .create_client()
+        .await
+        .unwrap()
+        .query_offset(name.clone(), &env.stream)
+        .await
+        .unwrap();
+    assert_eq!(4, stored);
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(b"message5".as_ref()), delivery.message().data());
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_store_offset_without_name_test() {
+    let env = TestEnvironment::create().await;
+
+    let consumer = env.env.consumer().build(&env.stream).await.unwrap();
+
+    let result = consumer.store_offset(1).await;
+    assert!(matches!(
+        result,
+        Err(ConsumerStoreOffsetError::NameMissing { .. })
+    ));
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumers_share_connection_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+
+    let mut first = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let mut second = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let _ = producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+
+    let first_delivery = first.next().await.unwrap().unwrap();
+    let second_delivery = second.next().await.unwrap().unwrap();
+
+    assert_ne!(
+        first_delivery.subscription_id(),
+        second_delivery.subscription_id()
+    );
+    assert_eq!(Some(b"message".as_ref()), first_delivery.message().data());
+    assert_eq!(Some(b"message".as_ref()), second_delivery.message().data());
+
+    first.handle().close().await.unwrap();
+
+    // the connection stays open for the remaining consumer
+    let _ = producer
+        .send_with_confirm(Message::builder().body(b"message2".to_vec()).build())
+        .await
+        .unwrap();
+    let delivery = second.next().await.unwrap().unwrap();
+    assert_eq!(Some(b"message2".as_ref()), delivery.message().data());
+
+    second.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
@@ -3,3 +3,4 @@ mod common;
 mod consumer_test;
 mod environment_test;
 mod producer_test;
+mod superstream_test;
@@ -1,7 +1,13 @@
+use std::{sync::Arc, time::Duration};
+
 use fake::{Fake, Faker};
 use futures::StreamExt;
-use rabbitmq_stream_client::types::{Message, OffsetSpecification};
-use tokio::sync::mpsc::channel;
+use rabbitmq_stream_client::{
+    error::ProducerPublishError,
+    types::{Compression, Message, OffsetSpecification},
+    Environment, RecoveryEvent,
+};
+use tokio::sync::{mpsc::channel, Mutex};
 
 use crate::common::TestEnvironment;
 
@@ -27,7 +33,7 @@ async fn producer_send_no_name_ok() {
     producer.close().await.unwrap();
 
     let delivery = consumer.next().await.unwrap().unwrap();
-    assert_eq!(1, delivery.subscription_id());
+    assert_eq!(0, delivery.subscription_id());
     assert_eq!(Some(b"message".as_ref()), delivery.message().data());
 
     consumer.handle().close().await.unwrap();
@@ -53,21 +59,28 @@ async fn producer_send_name_with_deduplication_ok() {
         .await
         .unwrap();
 
-    let _ = producer
+    let confirmation = producer
         .send_with_confirm(Message::builder().body(b"message0".to_vec()).build())
         .await
         .unwrap();
+    assert_eq!(1, confirmation.publishing_id());
 
     // this is not published
-    let _ = producer
+    let result = producer
         .send_with_confirm(
             Message::builder()
                 .body(b"message0".to_vec())
-                .publising_id(0)
+                .publising_id(1)
                 .build(),
         )
-        .await
-        .unwrap();
+        .await;
+    assert!(matches!(
+        result,
+        Err(ProducerPublishError::PublishingIdNotIncreasing {
+            publishing_id: 1,
+            last_publishing_id: 1
+        })
+    ));
 
     let _ = producer
         .send_with_confirm(Message::builder().body(b"message1".to_vec()).build())
@@ -77,15 +90,68 @@ async fn producer_send_name_with_deduplication_ok() {
     producer.close().await.unwrap();
 
     let delivery = consumer.next().await.unwrap().unwrap();
-    assert_eq!(1, delivery.subscription_id());
+    assert_eq!(0, delivery.subscription_id());
     assert_eq!(Some(b"message0".as_ref()), delivery.message().data());
 
     let delivery = consumer.next().await.unwrap().unwrap();
-    assert_eq!(1, delivery.subscription_id());
+    assert_eq!(0, delivery.subscription_id());
     assert_eq!(Some(b"message1".as_ref()), delivery.message().data());
 
     consumer.handle().close().await.unwrap();
 }
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_dedup_reports_duplicates_test() {
+    let env = TestEnvironment::create().await;
+    let reference: String = Faker.fake();
+
+    let producer = env
+        .env
+        .producer()
+        .name(&reference)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        let confirmation = producer
+            .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+            .await
+            .unwrap();
+        assert!(!confirmation.is_duplicate());
+    }
+    assert_eq!(Some(3), producer.last_publishing_id());
+    assert_eq!(3, producer.query_last_publishing_id().await.unwrap());
+    producer.close().await.unwrap();
+
+    // a new producer with the same name resends an already stored message
+    let producer = env
+        .env
+        .producer()
+        .name(&reference)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let confirmation = producer
+        .send_with_confirm(
+            Message::builder()
+                .body(b"message".to_vec())
+                .publising_id(2)
+                .build(),
+        )
+        .await
+        .unwrap();
+    assert!(confirmation.confirmed());
+    assert!(confirmation.is_duplicate());
+
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+    assert_eq!(4, confirmation.publishing_id());
+    assert!(!confirmation.is_duplicate());
+
+    producer.close().await.unwrap();
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn producer_send_with_callback() {
     let env = TestEnvironment::create().await;
@@ -100,7 +166,7 @@ async fn producer_send_with_callback() {
         .await
         .unwrap();
 
-    let _ = producer
+    producer
         .send(
             Message::builder().body(b"message".to_vec()).build(),
             move |confirm_result| {
@@ -115,7 +181,7 @@ async fn producer_send_with_callback() {
 
     let result = rx.recv().await.unwrap();
 
-    assert_eq!(0, result.unwrap().publishing_id());
+    assert_eq!(1, result.unwrap().publishing_id());
 
     producer.close().await.unwrap();
 }
@@ -127,7 +193,7 @@ async fn producer_batch_send_with_callback() {
     let (tx, mut rx) = channel(1);
     let producer = env.env.producer().build(&env.stream).await.unwrap();
 
-    let _ = producer
+    producer
         .batch_send(
             vec![Message::builder().body(b"message".to_vec()).build()],
             move |confirm_result| {
@@ -159,7 +225,324 @@ async fn producer_batch_send() {
         .unwrap();
 
     assert_eq!(1, result.len());
-    assert_eq!(0, result.get(0).unwrap().publishing_id());
+    assert_eq!(0, result.first().unwrap().publishing_id());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_send_then_batch_send_keeps_order_test() {
+    let env = TestEnvironment::create().await;
+    let reference: String = Faker.fake();
+
+    let producer = env
+        .env
+        .producer()
+        .name(&reference)
+        .batch_delay(Duration::from_secs(1))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    // waiting in the accumulator when the batch is sent
+    let (tx, mut rx) = channel(5);
+    for n in 0..5 {
+        let tx = tx.clone();
+        producer
+            .send(
+                Message::builder().body(format!("message{}", n)).build(),
+                move |confirmation| {
+                    let tx = tx.clone();
+                    async move { tx.send(confirmation).await.unwrap() }
+                },
+            )
+            .await
+            .unwrap();
+    }
+    let batch = producer
+        .batch_send_with_confirm(
+            (5..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    let mut publishing_ids = Vec::new();
+    for _ in 0..5 {
+        let confirmation = rx.recv().await.unwrap().unwrap();
+        assert!(confirmation.confirmed());
+        assert!(!confirmation.is_duplicate());
+        publishing_ids.push(confirmation.publishing_id());
+    }
+    for confirmation in &batch {
+        assert!(confirmation.confirmed());
+        assert!(!confirmation.is_duplicate());
+        publishing_ids.push(confirmation.publishing_id());
+    }
+    publishing_ids.sort_unstable();
+    assert_eq!((1..=10).collect::<Vec<u64>>(), publishing_ids);
+    assert_eq!(Some(10), producer.last_publishing_id());
+    assert_eq!(10, producer.query_last_publishing_id().await.unwrap());
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    for n in 0..10 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(
+            Some(format!("message{}", n).as_bytes()),
+            delivery.message().data()
+        );
+    }
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_send_sub_entries_ok() {
+    let env = TestEnvironment::create().await;
+
+    for compression in [
+        Compression::None,
+        Compression::Gzip,
+        Compression::Snappy,
+        Compression::Lz4,
+        Compression::Zstd,
+    ] {
+        let producer = env
+            .env
+            .producer()
+            .sub_entry_size(3)
+            .compression(compression)
+            .build(&env.stream)
+            .await
+            .unwrap();
+
+        let mut consumer = env
+            .env
+            .consumer()
+            .offset(OffsetSpecification::Next)
+            .build(&env.stream)
+            .await
+            .unwrap();
+
+        let messages: Vec<Message> = (0..5)
+            .map(|i| Message::builder().body(format!("message{}", i)).build())
+            .collect();
+
+        let result = producer.batch_send_with_confirm(messages).await.unwrap();
+
+        assert_eq!(5, result.len());
+        assert!(result.iter().all(|status| status.confirmed()));
+
+        for i in 0..5 {
+            let delivery = consumer.next().await.unwrap().unwrap();
+            assert_eq!(
+                Some(format!("message{}", i).as_bytes()),
+                delivery.message().data()
+            );
+        }
+
+        producer.close().await.unwrap();
+        consumer.handle().close().await.unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_max_in_flight_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env
+        .env
+        .producer()
+        .max_in_flight(2)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let messages: Vec<Message> = (0..5)
+        .map(|i| Message::builder().body(format!("message{}", i)).build())
+        .collect();
+    let result = producer.batch_send_with_confirm(messages).await.unwrap();
+    assert_eq!(5, result.len());
+
+    // the first message waits in the batch, so the second one goes over the limit
+    let limited = env
+        .env
+        .producer()
+        .max_in_flight(1)
+        .batch_delay(Duration::from_secs(1))
+        .build(&env.stream)
+        .await
+        .unwrap();
+    limited
+        .try_send(
+            Message::builder().body(b"first".to_vec()).build(),
+            |_| async {},
+        )
+        .await
+        .unwrap();
+    let result = limited
+        .try_send(
+            Message::builder().body(b"second".to_vec()).build(),
+            |_| async {},
+        )
+        .await;
+    assert!(matches!(
+        result,
+        Err(ProducerPublishError::MaxInFlight { max_in_flight: 1 })
+    ));
 
     producer.close().await.unwrap();
+    limited.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_confirm_timeout_test() {
+    let env = TestEnvironment::create().await;
+
+    // the message stays in the batch for longer than the timeout
+    let producer = env
+        .env
+        .producer()
+        .batch_delay(Duration::from_secs(2))
+        .confirm_timeout(Duration::from_millis(50))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let result = producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ProducerPublishError::Timeout { publishing_id: 0 })
+    ));
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_close_drain_test() {
+    let env = TestEnvironment::create().await;
+
+    // the messages are still in the batch when the producer is closed
+    let producer = env
+        .env
+        .producer()
+        .batch_delay(Duration::from_secs(10))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let (tx, mut rx) = channel(3);
+    for _ in 0..3 {
+        let tx = tx.clone();
+        producer
+            .send(
+                Message::builder().body(b"message".to_vec()).build(),
+                move |result| {
+                    let tx = tx.clone();
+                    async move {
+                        let _ = tx.send(result).await;
+                    }
+                },
+            )
+            .await
+            .unwrap();
+    }
+    drop(tx);
+
+    let summary = producer.close_drain(Duration::from_secs(5)).await.unwrap();
+
+    assert_eq!(3, summary.confirmed());
+    assert_eq!(0, summary.failed());
+    assert_eq!(0, summary.abandoned());
+
+    let mut confirmed = 0;
+    while let Some(result) = rx.recv().await {
+        assert!(result.unwrap().confirmed());
+        confirmed += 1;
+    }
+    assert_eq!(3, confirmed);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_close_fails_waiting_confirmations_test() {
+    let env = TestEnvironment::create().await;
+
+    // the message is still in the batch when the producer is closed
+    let producer = env
+        .env
+        .producer()
+        .batch_delay(Duration::from_secs(2))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let (tx, mut rx) = channel(1);
+    producer
+        .send(
+            Message::builder().body(b"message".to_vec()).build(),
+            move |result| {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send(result).await;
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+    producer.close().await.unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(result, Err(ProducerPublishError::Closed)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_stream_deleted_test() {
+    let stream: String = Faker.fake();
+    let events = Arc::new(Mutex::new(vec![]));
+    let cloned = events.clone();
+    let env = Environment::builder()
+        .recovery_listener(move |event| {
+            let events = cloned.clone();
+            async move { events.lock().await.push(event) }
+        })
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create(&stream).await.unwrap();
+
+    let producer = env.producer().build(&stream).await.unwrap();
+    env.delete_stream(&stream).await.unwrap();
+
+    for _ in 0..50 {
+        if producer.is_closed() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(producer.is_closed());
+    assert_eq!(
+        vec![RecoveryEvent::StreamDeleted {
+            stream: stream.clone()
+        }],
+        *events.lock().await
+    );
+
+    let result = producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await;
+    assert!(matches!(result, Err(ProducerPublishError::Closed)));
 }
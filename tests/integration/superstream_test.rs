@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+
+use fake::{Fake, Faker};
+use futures::StreamExt;
+use rabbitmq_stream_client::{
+    types::{Compression, Message, OffsetSpecification},
+    Environment, RoutingStrategy,
+};
+
+struct TestSuperStream {
+    env: Environment,
+    super_stream: String,
+}
+
+impl TestSuperStream {
+    async fn create(partitions: usize) -> TestSuperStream {
+        let super_stream: String = Faker.fake();
+        let env = Environment::builder().build().await.unwrap();
+        env.stream_creator()
+            .create_super_stream(&super_stream, partitions)
+            .await
+            .unwrap();
+
+        TestSuperStream { env, super_stream }
+    }
+}
+
+impl Drop for TestSuperStream {
+    fn drop(&mut self) {
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                self.env
+                    .delete_super_stream(&self.super_stream)
+                    .await
+                    .unwrap()
+            })
+        });
+    }
+}
+
+fn routing_key(message: &Message) -> String {
+    String::from_utf8(message.data().unwrap().to_vec()).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_hash_routing_test() {
+    let super_stream = TestSuperStream::create(3).await;
+    let message_count = 30;
+
+    let producer = super_stream
+        .env
+        .super_stream_producer(RoutingStrategy::hash(routing_key))
+        .build(&super_stream.super_stream)
+        .await
+        .unwrap();
+
+    assert_eq!(3, producer.partitions().len());
+
+    let mut consumer = super_stream
+        .env
+        .super_stream_consumer()
+        .offset(OffsetSpecification::First)
+        .build(&super_stream.super_stream)
+        .await
+        .unwrap();
+
+    for n in 0..message_count {
+        let confirmations = producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+        assert_eq!(1, confirmations.len());
+        assert!(confirmations[0].confirmed());
+    }
+
+    let mut streams = HashSet::new();
+    for _ in 0..message_count {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert!(producer
+            .partitions()
+            .contains(&delivery.stream().to_owned()));
+        streams.insert(delivery.stream().to_owned());
+    }
+    assert!(streams.len() > 1);
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_key_routing_test() {
+    let super_stream = TestSuperStream::create(3).await;
+
+    let producer = super_stream
+        .env
+        .super_stream_producer(RoutingStrategy::key(routing_key))
+        .build(&super_stream.super_stream)
+        .await
+        .unwrap();
+
+    let mut consumer = super_stream
+        .env
+        .super_stream_consumer()
+        .offset(OffsetSpecification::First)
+        .build(&super_stream.super_stream)
+        .await
+        .unwrap();
+
+    producer
+        .send_with_confirm(Message::builder().body("2").build())
+        .await
+        .unwrap();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(
+        format!("{}-2", super_stream.super_stream),
+        delivery.stream()
+    );
+
+    let unroutable = producer
+        .send_with_confirm(Message::builder().body("not-bound").build())
+        .await;
+    assert!(unroutable.is_err());
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_sub_entry_test() {
+    let super_stream = TestSuperStream::create(2).await;
+    let message_count = 20;
+
+    let producer = super_stream
+        .env
+        .super_stream_producer(RoutingStrategy::hash(routing_key))
+        .sub_entry_size(5)
+        .compression(Compression::Gzip)
+        .build(&super_stream.super_stream)
+        .await
+        .unwrap();
+
+    let mut consumer = super_stream
+        .env
+        .super_stream_consumer()
+        .offset(OffsetSpecification::First)
+        .build(&super_stream.super_stream)
+        .await
+        .unwrap();
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(message_count);
+    for n in 0..message_count {
+        let tx = tx.clone();
+        producer
+            .send(
+                Message::builder().body(format!("message{}", n)).build(),
+                move |confirmation| {
+                    let tx = tx.clone();
+                    async move {
+                        tx.send(confirmation.unwrap().confirmed()).await.unwrap();
+                    }
+                },
+            )
+            .await
+            .unwrap();
+    }
+    for _ in 0..message_count {
+        assert!(rx.recv().await.unwrap());
+    }
+
+    let mut bodies = HashSet::new();
+    for _ in 0..message_count {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        bodies.insert(routing_key(delivery.message()));
+    }
+    assert_eq!(message_count, bodies.len());
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}